@@ -0,0 +1,117 @@
+//! Capabilities of the codec interfaces provided by libvpx.
+
+use ffi;
+
+/// The set of capabilities of a particular encoder or decoder interface, corresponds to the
+/// `VPX_CODEC_CAP_*` flags returned by `vpx_codec_get_caps()`. Some of the bit values are shared
+/// between encoder and decoder capabilities in libvpx, so the flags are interpreted depending on
+/// the kind of the interface.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Capabilities {
+    encoder: bool,
+    decoder: bool,
+
+    // Decoder specific capabilities.
+    put_slice: bool,
+    put_frame: bool,
+    postproc: bool,
+    error_concealment: bool,
+    input_fragments: bool,
+    frame_threading: bool,
+    external_frame_buffer: bool,
+
+    // Encoder specific capabilities.
+    psnr: bool,
+    output_partition: bool,
+    highbitdepth: bool,
+}
+
+impl Capabilities {
+    /// Interprets the capabilities of an encoder interface.
+    pub fn from_encoder_caps(caps: ffi::vpx_codec_caps_t) -> Self {
+        let has = |flag: u32| caps & flag as ffi::vpx_codec_caps_t != 0;
+        Capabilities {
+            encoder: has(ffi::VPX_CODEC_CAP_ENCODER),
+            decoder: has(ffi::VPX_CODEC_CAP_DECODER),
+            psnr: has(ffi::VPX_CODEC_CAP_PSNR),
+            output_partition: has(ffi::VPX_CODEC_CAP_OUTPUT_PARTITION),
+            highbitdepth: has(ffi::VPX_CODEC_CAP_HIGHBITDEPTH),
+            ..Default::default()
+        }
+    }
+
+    /// Interprets the capabilities of a decoder interface.
+    pub fn from_decoder_caps(caps: ffi::vpx_codec_caps_t) -> Self {
+        let has = |flag: u32| caps & flag as ffi::vpx_codec_caps_t != 0;
+        Capabilities {
+            encoder: has(ffi::VPX_CODEC_CAP_ENCODER),
+            decoder: has(ffi::VPX_CODEC_CAP_DECODER),
+            put_slice: has(ffi::VPX_CODEC_CAP_PUT_SLICE),
+            put_frame: has(ffi::VPX_CODEC_CAP_PUT_FRAME),
+            postproc: has(ffi::VPX_CODEC_CAP_POSTPROC),
+            error_concealment: has(ffi::VPX_CODEC_CAP_ERROR_CONCEALMENT),
+            input_fragments: has(ffi::VPX_CODEC_CAP_INPUT_FRAGMENTS),
+            frame_threading: has(ffi::VPX_CODEC_CAP_FRAME_THREADING),
+            external_frame_buffer: has(ffi::VPX_CODEC_CAP_EXTERNAL_FRAME_BUFFER),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_encoder(&self) -> bool {
+        self.encoder
+    }
+
+    pub fn is_decoder(&self) -> bool {
+        self.decoder
+    }
+
+    /// Decoder can process slices (partial frames).
+    pub fn put_slice(&self) -> bool {
+        self.put_slice
+    }
+
+    /// Decoder can output frames as soon as they are decoded.
+    pub fn put_frame(&self) -> bool {
+        self.put_frame
+    }
+
+    /// Decoder supports postprocessing.
+    pub fn postproc(&self) -> bool {
+        self.postproc
+    }
+
+    /// Decoder can conceal the errors caused by lost or corrupted frames.
+    pub fn error_concealment(&self) -> bool {
+        self.error_concealment
+    }
+
+    /// Decoder can receive the coded frames in fragments.
+    pub fn input_fragments(&self) -> bool {
+        self.input_fragments
+    }
+
+    /// Decoder supports frame-based multithreading.
+    pub fn frame_threading(&self) -> bool {
+        self.frame_threading
+    }
+
+    /// Decoder can use external frame buffers.
+    pub fn external_frame_buffer(&self) -> bool {
+        self.external_frame_buffer
+    }
+
+    /// Encoder can calculate PSNR of the encoded frames.
+    pub fn psnr(&self) -> bool {
+        self.psnr
+    }
+
+    /// Encoder can output one partition at a time.
+    pub fn output_partition(&self) -> bool {
+        self.output_partition
+    }
+
+    /// Encoder supports high bit-depth input.
+    pub fn highbitdepth(&self) -> bool {
+        self.highbitdepth
+    }
+}
@@ -0,0 +1,372 @@
+//! Pure Rust conversion between the packed RGB/YUV formats (e.g. the output of a screen capture)
+//! and the planar YUV formats the encoders accept.
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+use ffi;
+
+use error::{CodecError, Error, VPXResult as Result};
+use image::{ColorRange, ColorSpace, Format, Image, enum_value};
+
+/// An image in one of the packed formats: `RGB24`, `BGR24`, `ARGB`, `BGRA`, `RGB565`, `YUY2`,
+/// `UYVY` or `YVYU`. The alpha channel is ignored.
+pub struct PackedImage<'data> {
+    data: &'data [u8],
+    layout: Layout,
+    width: u32,
+    height: u32,
+    stride: usize,
+}
+
+impl<'data> PackedImage<'data> {
+    /// Wraps the image data, `stride` being the number of bytes between the beginnings of two
+    /// consecutive rows. Fails if the format is not a packed one or the data is too small.
+    pub fn new(data: &'data [u8], format: Format, width: u32, height: u32, stride: usize)
+        -> Result<Self>
+    {
+        let layout = match Layout::new(format) {
+            Some(layout) => layout,
+            None => return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "PackedImage::new",
+                format!("{:?} is not a supported packed format", format))),
+        };
+        let row_size = layout.row_size(width);
+        let required = stride.checked_mul(height.saturating_sub(1) as usize)
+                             .and_then(|size| size.checked_add(row_size));
+        if width == 0 || height == 0 || stride < row_size ||
+           required.map_or(true, |required| data.len() < required) {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "PackedImage::new",
+                format!("the data is too small for a {}x{} {:?} image", width, height, format)));
+        }
+
+        Ok(PackedImage {
+            data: data,
+            layout: layout,
+            width: width,
+            height: height,
+            stride: stride,
+        })
+    }
+
+    /// Converts the image to the full resolution Y, U and V planes.
+    fn to_planes(&self, matrix: &Matrix) -> [Vec<u8>; 3] {
+        let width = self.width as usize;
+        let size = width * self.height as usize;
+        let mut planes = [vec![0; size], vec![0; size], vec![0; size]];
+        for (y, row) in self.data.chunks(self.stride).take(self.height as usize).enumerate() {
+            for x in 0..width {
+                let (luma, u, v) = self.layout.read(row, x, matrix);
+                planes[0][y * width + x] = luma;
+                planes[1][y * width + x] = u;
+                planes[2][y * width + x] = v;
+            }
+        }
+        planes
+    }
+}
+
+/// Converts the packed image to an 8-bit I420 image, the chroma being averaged over each 2x2
+/// block. The RGB formats are converted with the matrix of the color space (BT.709, BT.2020,
+/// SMPTE 240M or BT.601 otherwise); the YUV formats are only repacked. With `SRGB` the planes
+/// hold the G, B and R components unchanged (the range is ignored).
+pub fn to_i420(src: &PackedImage, color_space: ColorSpace, range: ColorRange) -> Image<'static> {
+    let planes = src.to_planes(&Matrix::new(color_space, range));
+    let (width, height) = (src.width as usize, src.height as usize);
+    let (chroma_width, chroma_height) = ((width + 1) / 2, (height + 1) / 2);
+
+    // Same layout as `vpx_img_wrap()` produces with the stride alignment of 2.
+    let stride = (width + 1) & !1;
+    let luma_size = stride * ((height + 1) & !1);
+    let chroma_stride = stride / 2;
+    let chroma_size = luma_size / 4;
+    let mut data = vec![0u8; luma_size + 2 * chroma_size];
+    copy_plane(&planes[0], width, height, &mut data[..luma_size], stride);
+    for (plane, offset) in planes[1..].iter().zip(vec![luma_size, luma_size + chroma_size]) {
+        let dst = &mut data[offset..offset + chroma_size];
+        for cy in 0..chroma_height {
+            for cx in 0..chroma_width {
+                let mut sum = 0;
+                let mut count = 0;
+                for y in 2 * cy..(2 * cy + 2).min(height) {
+                    for x in 2 * cx..(2 * cx + 2).min(width) {
+                        sum += plane[y * width + x] as usize;
+                        count += 1;
+                    }
+                }
+                dst[cy * chroma_stride + cx] = ((sum + count / 2) / count) as u8;
+            }
+        }
+    }
+
+    let mut image = Image::new(Cow::Owned(data), Format::I420 { hi_bit_depth: false },
+                               color_space, src.width, src.height, 2);
+    image.set_color_range(range);
+    image
+}
+
+/// Converts the packed image to an 8-bit I444 image, see `to_i420()`. The chroma of the packed
+/// YUV formats is duplicated horizontally.
+pub fn to_i444(src: &PackedImage, color_space: ColorSpace, range: ColorRange) -> Image<'static> {
+    let planes = src.to_planes(&Matrix::new(color_space, range));
+    let (width, height) = (src.width as usize, src.height as usize);
+
+    // Same layout as `vpx_img_wrap()` produces with the stride alignment of 1.
+    let plane_size = width * height;
+    let mut data = vec![0u8; 3 * plane_size];
+    for (plane, dst) in planes.iter().zip(data.chunks_mut(plane_size)) {
+        copy_plane(plane, width, height, dst, width);
+    }
+
+    let mut image = Image::new(Cow::Owned(data), Format::I444 { hi_bit_depth: false },
+                               color_space, src.width, src.height, 1);
+    image.set_color_range(range);
+    image
+}
+
+/// Converts an 8-bit planar YUV image (e.g. a `DecodedImage` or an `Image`) to one of the packed
+/// formats, see `PackedImage`. The color space and the range of the image select the conversion
+/// matrix. The rows of the result are not padded.
+///
+/// # Safety
+///
+/// The planes of the image have to be readable for `d_w` x `d_h` samples (subsampled by the
+/// chroma shifts for the chroma planes) with the strides of the image. This holds for the images
+/// produced by the decoder, and for an `Image` if its data is large enough for its format and
+/// dimensions (which `Image::new()` does not check).
+pub unsafe fn to_packed(image: &ffi::vpx_image_t, format: Format) -> Result<Vec<u8>> {
+    let fmt = enum_value(&image.fmt);
+    let planar = (fmt & ffi::VPX_IMG_FMT_PLANAR) != 0;
+    let high_bit_depth = (fmt & ffi::VPX_IMG_FMT_HIGHBITDEPTH) != 0;
+    let layout = match Layout::new(format) {
+        Some(layout) if planar && !high_bit_depth => layout,
+        _ => return Err(Error::with_detail(
+            CodecError::InvalidParam,
+            "convert::to_packed",
+            format!("cannot convert the format {} to {:?}", fmt, format))),
+    };
+
+    let color_space = ColorSpace::try_from(enum_value(&image.cs))?;
+    let range = if enum_value(&image.range) == ffi::vpx_color_range::VPX_CR_FULL_RANGE as u32 {
+        ColorRange::Full
+    } else {
+        ColorRange::Studio
+    };
+    let matrix = Matrix::new(color_space, range);
+
+    let (width, height) = (image.d_w as usize, image.d_h as usize);
+    let sample = |plane: usize, x: usize, y: usize| -> u8 {
+        let (x, y) = if plane == 0 {
+            (x, y)
+        } else {
+            (x >> image.x_chroma_shift, y >> image.y_chroma_shift)
+        };
+        let offset = y as isize * image.stride[plane] as isize + x as isize;
+        *image.planes[plane].offset(offset)
+    };
+
+    let row_size = layout.row_size(image.d_w);
+    let mut result = vec![0u8; row_size * height];
+    for (y, row) in result.chunks_mut(row_size).enumerate() {
+        for x in 0..width {
+            let yuv = (sample(0, x, y), sample(1, x, y), sample(2, x, y));
+            layout.write(row, x, yuv, &matrix);
+        }
+    }
+    Ok(result)
+}
+
+/// The byte layout of a packed format.
+#[derive(Copy, Clone, Debug)]
+enum Layout {
+    Rgb(RgbLayout),
+    // Two 4:2:2 pixels in 4 bytes given by the offsets of the first luma sample and of the chroma
+    // samples, the second luma sample is 2 bytes after the first one.
+    Yuv422 { luma: usize, u: usize, v: usize },
+}
+
+#[derive(Copy, Clone, Debug)]
+enum RgbLayout {
+    Rgb24,
+    Bgr24,
+    Argb,
+    Bgra,
+    Rgb565 { le: bool },
+}
+
+impl Layout {
+    /// Returns the layout of the packed format, `None` for the other formats.
+    fn new(format: Format) -> Option<Self> {
+        match format {
+            Format::RGB24 => Some(Layout::Rgb(RgbLayout::Rgb24)),
+            Format::BGR24 => Some(Layout::Rgb(RgbLayout::Bgr24)),
+            Format::ARGB => Some(Layout::Rgb(RgbLayout::Argb)),
+            Format::BGRA => Some(Layout::Rgb(RgbLayout::Bgra)),
+            Format::RGB565 { le } => Some(Layout::Rgb(RgbLayout::Rgb565 { le: le })),
+            Format::YUY2 => Some(Layout::Yuv422 { luma: 0, u: 1, v: 3 }),
+            Format::UYVY => Some(Layout::Yuv422 { luma: 1, u: 0, v: 2 }),
+            Format::YVYU => Some(Layout::Yuv422 { luma: 0, u: 3, v: 1 }),
+            _ => None,
+        }
+    }
+
+    /// Returns the size in bytes of a row of the given width.
+    fn row_size(&self, width: u32) -> usize {
+        let width = width as usize;
+        match *self {
+            Layout::Rgb(RgbLayout::Rgb24) | Layout::Rgb(RgbLayout::Bgr24) => width * 3,
+            Layout::Rgb(RgbLayout::Argb) | Layout::Rgb(RgbLayout::Bgra) => width * 4,
+            Layout::Rgb(RgbLayout::Rgb565 { .. }) => width * 2,
+            Layout::Yuv422 { .. } => (width + 1) / 2 * 4,
+        }
+    }
+
+    /// Reads the YUV components of the pixel of a row, converting RGB with the matrix.
+    fn read(&self, row: &[u8], x: usize, matrix: &Matrix) -> (u8, u8, u8) {
+        match *self {
+            Layout::Rgb(rgb) => {
+                let (r, g, b) = rgb.read(row, x);
+                matrix.to_yuv(r, g, b)
+            },
+            Layout::Yuv422 { luma, u, v } => {
+                let pair = &row[x / 2 * 4..x / 2 * 4 + 4];
+                (pair[luma + x % 2 * 2], pair[u], pair[v])
+            },
+        }
+    }
+
+    /// Writes the YUV components of the pixel to a row, converting them to RGB with the matrix.
+    /// The pixels of a row have to be written in order: the chroma of a 4:2:2 pair is the average
+    /// of its two pixels, the odd pixel being averaged with what the even one has written.
+    fn write(&self, row: &mut [u8], x: usize, (y, u, v): (u8, u8, u8), matrix: &Matrix) {
+        match *self {
+            Layout::Rgb(rgb) => rgb.write(row, x, matrix.to_rgb(y, u, v)),
+            Layout::Yuv422 { luma, u: u_offset, v: v_offset } => {
+                let pair = &mut row[x / 2 * 4..x / 2 * 4 + 4];
+                pair[luma + x % 2 * 2] = y;
+                if x % 2 == 0 {
+                    pair[u_offset] = u;
+                    pair[v_offset] = v;
+                } else {
+                    pair[u_offset] = average(pair[u_offset], u);
+                    pair[v_offset] = average(pair[v_offset], v);
+                }
+            },
+        }
+    }
+}
+
+impl RgbLayout {
+    /// Reads the RGB components of the pixel of a row.
+    fn read(&self, row: &[u8], x: usize) -> (u8, u8, u8) {
+        match *self {
+            RgbLayout::Rgb24 => (row[x * 3], row[x * 3 + 1], row[x * 3 + 2]),
+            RgbLayout::Bgr24 => (row[x * 3 + 2], row[x * 3 + 1], row[x * 3]),
+            RgbLayout::Argb => (row[x * 4 + 1], row[x * 4 + 2], row[x * 4 + 3]),
+            RgbLayout::Bgra => (row[x * 4 + 2], row[x * 4 + 1], row[x * 4]),
+            RgbLayout::Rgb565 { le } => {
+                let (low, high) = if le { (row[x * 2], row[x * 2 + 1]) }
+                                  else { (row[x * 2 + 1], row[x * 2]) };
+                let value = (high as u16) << 8 | low as u16;
+                let (r, g, b) =
+                    ((value >> 11) as u8, (value >> 5) as u8 & 0x3f, value as u8 & 0x1f);
+                (r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2)
+            },
+        }
+    }
+
+    /// Writes the RGB components of the pixel to a row, the alpha is set to opaque.
+    fn write(&self, row: &mut [u8], x: usize, (r, g, b): (u8, u8, u8)) {
+        match *self {
+            RgbLayout::Rgb24 => row[x * 3..x * 3 + 3].copy_from_slice(&[r, g, b]),
+            RgbLayout::Bgr24 => row[x * 3..x * 3 + 3].copy_from_slice(&[b, g, r]),
+            RgbLayout::Argb => row[x * 4..x * 4 + 4].copy_from_slice(&[0xff, r, g, b]),
+            RgbLayout::Bgra => row[x * 4..x * 4 + 4].copy_from_slice(&[b, g, r, 0xff]),
+            RgbLayout::Rgb565 { le } => {
+                let value = ((r as u16) >> 3) << 11 | ((g as u16) >> 2) << 5 | (b as u16) >> 3;
+                let (low, high) = (value as u8, (value >> 8) as u8);
+                let bytes = if le { [low, high] } else { [high, low] };
+                row[x * 2..x * 2 + 2].copy_from_slice(&bytes);
+            },
+        }
+    }
+}
+
+/// Copies a plane without padding into a destination with the given stride.
+fn copy_plane(src: &[u8], width: usize, height: usize, dst: &mut [u8], stride: usize) {
+    for (src, dst) in src.chunks(width).zip(dst.chunks_mut(stride)).take(height) {
+        dst[..width].copy_from_slice(src);
+    }
+}
+
+/// The conversion between R'G'B' and Y'CbCr as defined by the luma coefficients of the color
+/// space. `SRGB` is the identity: the planes hold G, B and R in full range.
+struct Matrix {
+    identity: bool,
+    kr: f32,
+    kb: f32,
+    // Scale and offset of the luma, scale of the chroma.
+    luma_scale: f32,
+    luma_offset: f32,
+    chroma_scale: f32,
+}
+
+impl Matrix {
+    fn new(color_space: ColorSpace, range: ColorRange) -> Self {
+        let (kr, kb) = match color_space {
+            ColorSpace::BT709 => (0.2126, 0.0722),
+            ColorSpace::BT2020 => (0.2627, 0.0593),
+            ColorSpace::SMPTE240 => (0.212, 0.087),
+            _ => (0.299, 0.114),
+        };
+        let (luma_scale, luma_offset, chroma_scale) = match range {
+            ColorRange::Studio => (219.0, 16.0, 224.0),
+            ColorRange::Full => (255.0, 0.0, 255.0),
+        };
+        Matrix {
+            identity: color_space == ColorSpace::SRGB,
+            kr: kr,
+            kb: kb,
+            luma_scale: luma_scale,
+            luma_offset: luma_offset,
+            chroma_scale: chroma_scale,
+        }
+    }
+
+    fn to_yuv(&self, r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+        if self.identity {
+            return (g, b, r);
+        }
+        let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+        let y = self.kr * r + (1.0 - self.kr - self.kb) * g + self.kb * b;
+        let pb = (b - y) / (2.0 * (1.0 - self.kb));
+        let pr = (r - y) / (2.0 * (1.0 - self.kr));
+        (clamp(self.luma_offset + self.luma_scale * y),
+         clamp(128.0 + self.chroma_scale * pb),
+         clamp(128.0 + self.chroma_scale * pr))
+    }
+
+    fn to_rgb(&self, y: u8, u: u8, v: u8) -> (u8, u8, u8) {
+        if self.identity {
+            return (v, y, u);
+        }
+        let y = (y as f32 - self.luma_offset) / self.luma_scale;
+        let pb = (u as f32 - 128.0) / self.chroma_scale;
+        let pr = (v as f32 - 128.0) / self.chroma_scale;
+        let r = y + 2.0 * (1.0 - self.kr) * pr;
+        let b = y + 2.0 * (1.0 - self.kb) * pb;
+        let g = (y - self.kr * r - self.kb * b) / (1.0 - self.kr - self.kb);
+        (clamp(r * 255.0), clamp(g * 255.0), clamp(b * 255.0))
+    }
+}
+
+fn average(a: u8, b: u8) -> u8 {
+    ((a as u16 + b as u16 + 1) / 2) as u8
+}
+
+fn clamp(value: f32) -> u8 {
+    value.round().max(0.0).min(255.0) as u8
+}
@@ -0,0 +1,95 @@
+//! Decoding of the color and alpha streams produced by `AlphaEncoder`.
+
+use decoder::{DecodedFramesIter, DecodedImage, Decoder, VpxDecoder};
+use error::VPXResult as Result;
+
+/// Decodes the color stream and the alpha stream side by side and recombines the decoded images.
+pub struct AlphaDecoder<Dec: VpxDecoder> {
+    color: Decoder<Dec>,
+    alpha: Decoder<Dec>,
+    has_alpha: bool,
+}
+
+impl<Dec: VpxDecoder> AlphaDecoder<Dec> {
+    pub fn new() -> Result<Self> {
+        Ok(AlphaDecoder {
+            color: Decoder::new()?,
+            alpha: Decoder::new()?,
+            has_alpha: false,
+        })
+    }
+
+    /// Decodes a frame of the color stream and the matching frame of the alpha stream (i.e. the
+    /// `BlockAdditional` of the WebM block) if there is one. Once an alpha frame is missing, the
+    /// alpha stream can only be decoded again starting from a keyframe. If only the alpha frame
+    /// fails to decode, the color images are still returned by `frames_iter()`, without alpha.
+    pub fn decode(&mut self, color: &[u8], alpha: Option<&[u8]>) -> Result<()> {
+        self.has_alpha = false;
+        self.color.decode(color)?;
+        if let Some(alpha) = alpha {
+            self.alpha.decode(alpha)?;
+            self.has_alpha = true;
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator over the images decoded by the last `decode()` call.
+    pub fn frames_iter(&mut self) -> AlphaDecodedFramesIter {
+        AlphaDecodedFramesIter {
+            color: self.color.frames_iter(),
+            alpha: if self.has_alpha { Some(self.alpha.frames_iter()) } else { None },
+        }
+    }
+}
+
+/// A decoded color image together with its alpha plane.
+pub struct AlphaDecodedImage<'decoder> {
+    color: DecodedImage<'decoder>,
+    alpha: Option<DecodedImage<'decoder>>,
+}
+
+impl<'decoder> AlphaDecodedImage<'decoder> {
+    /// The color image, its planes are the Y, U and V planes of the recombined image.
+    pub fn color(&self) -> &DecodedImage<'decoder> {
+        &self.color
+    }
+
+    /// The alpha plane (the luma of the alpha image) including the padding at the end of each
+    /// row, `None` if the frame has no alpha.
+    pub fn alpha(&self) -> Option<&'decoder [u8]> {
+        self.alpha.as_ref().map(|alpha| alpha.plane(0))
+    }
+
+    /// The number of bytes between the beginnings of two consecutive rows of the alpha plane.
+    pub fn alpha_stride(&self) -> usize {
+        self.alpha.as_ref().map_or(0, |alpha| alpha.stride(0))
+    }
+
+    /// Returns the data of the plane (Y, U, V, alpha) of the recombined image, see
+    /// `DecodedImage::plane()`. The alpha plane is empty if the frame has no alpha.
+    pub fn plane(&self, plane: usize) -> &'decoder [u8] {
+        if plane == 3 {
+            self.alpha().unwrap_or(&[])
+        } else {
+            self.color.plane(plane)
+        }
+    }
+}
+
+pub struct AlphaDecodedFramesIter<'decoder> {
+    color: DecodedFramesIter<'decoder>,
+    alpha: Option<DecodedFramesIter<'decoder>>,
+}
+
+impl<'decoder> Iterator for AlphaDecodedFramesIter<'decoder> {
+    type Item = AlphaDecodedImage<'decoder>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let color = self.color.next()?;
+        let alpha = self.alpha.as_mut().and_then(|alpha| alpha.next());
+        Some(AlphaDecodedImage {
+            color: color,
+            alpha: alpha,
+        })
+    }
+}
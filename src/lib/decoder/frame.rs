@@ -0,0 +1,112 @@
+use std::convert::TryFrom;
+use std::ops::Deref;
+use std::ptr::null_mut;
+use std::slice;
+
+use ffi;
+
+use error::VPXResult as Result;
+use image::{ColorSpace, Format, enum_value};
+
+/// An image produced by the decoder. It is owned by the decoder and is only valid until the next
+/// `Decoder::decode()` call.
+pub struct DecodedImage<'decoder> {
+    inner: &'decoder ffi::vpx_image_t,
+}
+
+impl<'decoder> DecodedImage<'decoder> {
+    pub fn width(&self) -> u32 {
+        self.inner.d_w
+    }
+
+    pub fn height(&self) -> u32 {
+        self.inner.d_h
+    }
+
+    pub fn bit_depth(&self) -> u32 {
+        self.inner.bit_depth
+    }
+
+    /// The format of the image, fails for the libvpx formats `Format` does not cover.
+    pub fn format(&self) -> Result<Format> {
+        Format::try_from(enum_value(&self.inner.fmt))
+    }
+
+    pub fn color_space(&self) -> Result<ColorSpace> {
+        ColorSpace::try_from(enum_value(&self.inner.cs))
+    }
+
+    /// The number of bytes between the beginnings of two consecutive rows of the plane.
+    pub fn stride(&self, plane: usize) -> usize {
+        self.inner.stride[plane] as usize
+    }
+
+    /// The width of the plane in samples, taking the chroma subsampling into account.
+    pub fn plane_width(&self, plane: usize) -> usize {
+        let shift = if plane == 1 || plane == 2 { self.inner.x_chroma_shift } else { 0 };
+        ((self.inner.d_w + shift) >> shift) as usize
+    }
+
+    /// The height of the plane in rows, taking the chroma subsampling into account.
+    pub fn plane_height(&self, plane: usize) -> usize {
+        let shift = if plane == 1 || plane == 2 { self.inner.y_chroma_shift } else { 0 };
+        ((self.inner.d_h + shift) >> shift) as usize
+    }
+
+    /// Returns the data of the plane (Y, U, V, alpha) including the padding at the end of each
+    /// row. The plane is empty if the image does not have it.
+    pub fn plane(&self, plane: usize) -> &'decoder [u8] {
+        let ptr = self.inner.planes[plane];
+        if ptr.is_null() {
+            return &[];
+        }
+        let len = self.stride(plane) * self.plane_height(plane);
+        unsafe { slice::from_raw_parts(ptr, len) }
+    }
+
+    /// Whether the samples are stored in 16 bits, see `plane_high_bit_depth()`.
+    pub fn is_high_bit_depth(&self) -> bool {
+        (enum_value(&self.inner.fmt) & ffi::VPX_IMG_FMT_HIGHBITDEPTH) != 0
+    }
+
+    /// Returns the samples of the plane of a high bit-depth image, see `plane()`. The stride in
+    /// samples is half of `stride()`. The plane is empty if the image is not a high bit-depth one.
+    pub fn plane_high_bit_depth(&self, plane: usize) -> &'decoder [u16] {
+        let data = self.plane(plane);
+        if !self.is_high_bit_depth() || data.is_empty() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(data.as_ptr() as *const u16, data.len() / 2) }
+    }
+}
+
+impl<'decoder> Deref for DecodedImage<'decoder> {
+    type Target = ffi::vpx_image_t;
+
+    fn deref(&self) -> &ffi::vpx_image_t {
+        self.inner
+    }
+}
+
+pub struct DecodedFramesIter<'decoder> {
+    inner: ffi::vpx_codec_iter_t,
+    codec_ctx: &'decoder mut ffi::vpx_codec_ctx_t,
+}
+
+impl<'decoder> DecodedFramesIter<'decoder> {
+    pub fn new(ctx: &'decoder mut ffi::vpx_codec_ctx_t) -> Self {
+        DecodedFramesIter {
+            inner: null_mut(),
+            codec_ctx: ctx,
+        }
+    }
+}
+
+impl<'decoder> Iterator for DecodedFramesIter<'decoder> {
+    type Item = DecodedImage<'decoder>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let img = unsafe { ffi::vpx_codec_get_frame(self.codec_ctx, &mut self.inner) };
+        unsafe { img.as_ref().map(|img_ref| DecodedImage { inner: img_ref }) }
+    }
+}
@@ -0,0 +1,125 @@
+//! Common video decoder functions for VP8/VP9 codecs.
+
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr::{null, null_mut};
+
+use ffi;
+use libc;
+
+use capabilities::Capabilities;
+use context::CodecContext as Context;
+use error::{CodecError, Error, VPXResult as Result, check_err, check_ctx_err};
+
+pub use self::alpha::{AlphaDecodedFramesIter, AlphaDecodedImage, AlphaDecoder};
+pub use self::frame::{DecodedImage, DecodedFramesIter};
+
+mod alpha;
+mod frame;
+pub mod vp8;
+pub mod vp9;
+
+/// This trait has to be implemented by every codec which can be decoded by libvpx library.
+pub trait VpxDecoder {
+    /// Returns a reference to the decoder interface (some sort of opaque data structure inside
+    /// libvpx).
+    fn interface() -> *mut ffi::vpx_codec_iface_t;
+}
+
+/// Returns the capabilities of the decoder for the given codec.
+pub fn capabilities<Dec: VpxDecoder>() -> Capabilities {
+    Capabilities::from_decoder_caps(unsafe { ffi::vpx_codec_get_caps(Dec::interface()) })
+}
+
+/// An instance of libvpx-based decoder, the supported decoders are: `Decoder<VP8>`,
+/// `Decoder<VP9>`.
+pub struct Decoder<Dec: VpxDecoder> {
+    context: Context,
+    _phantom: PhantomData<Dec>,
+}
+
+impl<Dec: VpxDecoder> Decoder<Dec> {
+    /// Creates a new decoder with the default configuration.
+    pub fn new() -> Result<Self> {
+        let mut ctx = Context::new();
+        let res = unsafe { ffi::vpx_codec_dec_init_ver(&mut *ctx,
+                                                       Dec::interface(),
+                                                       null(),
+                                                       0,
+                                                       ffi::VPX_DECODER_ABI_VERSION as i32) };
+        check_ctx_err(&mut ctx, res, "vpx_codec_dec_init")?;
+        Ok(Decoder {
+            context: ctx,
+            _phantom: PhantomData { },
+        })
+    }
+
+    /// Decodes a single compressed frame (`vpx_codec_decode()`). The decoded images can be
+    /// obtained with `frames_iter()` afterwards.
+    pub fn decode(&mut self, data: &[u8]) -> Result<()> {
+        if data.len() > libc::c_uint::max_value() as usize {
+            return Err(Error::new(CodecError::InvalidParam, "vpx_codec_decode"));
+        }
+
+        let res = unsafe { ffi::vpx_codec_decode(&mut *self.context,
+                                                 data.as_ptr(),
+                                                 data.len() as libc::c_uint,
+                                                 null_mut(),
+                                                 0) };
+        check_ctx_err(&mut self.context, res, "vpx_codec_decode")?;
+        Ok(())
+    }
+
+    /// Returns an iterator over the images decoded by the last `decode()` call. You cannot call
+    /// `decode()` while you own the iterator, since the images are owned by the decoder.
+    pub fn frames_iter(&mut self) -> DecodedFramesIter {
+        DecodedFramesIter::new(&mut self.context)
+    }
+}
+
+/// Basic properties of the stream which can be obtained from the beginning of the bitstream
+/// without creating a decoder instance.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct StreamInfo {
+    width: u32,
+    height: u32,
+    is_keyframe: bool,
+}
+
+impl StreamInfo {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Whether the given data starts with a keyframe. The width and height are only meaningful
+    /// if this is `true`.
+    pub fn is_keyframe(&self) -> bool {
+        self.is_keyframe
+    }
+}
+
+/// Parses the beginning of the compressed `data` and returns the basic stream information.
+/// Wraps `vpx_codec_peek_stream_info()`, which does not require a decoder to be created, so it is
+/// cheap enough to be used for validating the input before the decoding.
+pub fn peek_stream_info<Dec: VpxDecoder>(data: &[u8]) -> Result<StreamInfo> {
+    if data.len() > libc::c_uint::max_value() as usize {
+        return Err(Error::new(CodecError::InvalidParam, "vpx_codec_peek_stream_info"));
+    }
+
+    let mut si: ffi::vpx_codec_stream_info_t = Default::default();
+    si.sz = mem::size_of::<ffi::vpx_codec_stream_info_t>() as libc::c_uint;
+    check_err(unsafe { ffi::vpx_codec_peek_stream_info(Dec::interface(),
+                                                       data.as_ptr(),
+                                                       data.len() as libc::c_uint,
+                                                       &mut si) },
+              "vpx_codec_peek_stream_info")?;
+    Ok(StreamInfo {
+        width: si.w,
+        height: si.h,
+        is_keyframe: si.is_kf != 0,
+    })
+}
@@ -0,0 +1,11 @@
+use ffi;
+
+use decoder::VpxDecoder;
+
+pub use encoder::vp8::VP8;
+
+impl VpxDecoder for VP8 {
+    fn interface() -> *mut ffi::vpx_codec_iface_t {
+        unsafe { &mut ffi::vpx_codec_vp8_dx_algo }
+    }
+}
@@ -0,0 +1,11 @@
+use ffi;
+
+use decoder::VpxDecoder;
+
+pub use encoder::vp9::VP9;
+
+impl VpxDecoder for VP9 {
+    fn interface() -> *mut ffi::vpx_codec_iface_t {
+        unsafe { &mut ffi::vpx_codec_vp9_dx_algo }
+    }
+}
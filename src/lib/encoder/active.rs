@@ -0,0 +1,148 @@
+//! Active maps, which let the encoder skip the regions of the frame which have not changed.
+
+use std::cmp;
+use std::ptr::null_mut;
+
+use ffi;
+
+use encoder::{Encoder, VpxEncoder};
+use encoder::roi::blocks;
+use encoder::vp9::VP9;
+use error::{CodecError, Error, VPXResult as Result};
+
+/// The size of the (square) block in pixels an `ActiveMap` consists of.
+pub const ACTIVE_MAP_BLOCK_SIZE: u32 = 16;
+
+/// Marks each 16x16 macroblock of the frame as active or inactive, wraps `vpx_active_map_t`. The
+/// inactive macroblocks are not encoded (copied from the previous frame), which saves CPU and
+/// bandwidth for the static regions (e.g. of a screen capture).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ActiveMap {
+    rows: u32,
+    cols: u32,
+    active: Vec<u8>,
+}
+
+impl ActiveMap {
+    /// Creates a map for a frame of the given dimensions in pixels with all macroblocks active.
+    pub fn new(width: u32, height: u32) -> Self {
+        let rows = blocks(height as u64, ACTIVE_MAP_BLOCK_SIZE) as u32;
+        let cols = blocks(width as u64, ACTIVE_MAP_BLOCK_SIZE) as u32;
+        ActiveMap {
+            rows: rows,
+            cols: cols,
+            active: vec![1; rows as usize * cols as usize],
+        }
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    pub fn cols(&self) -> u32 {
+        self.cols
+    }
+
+    pub fn is_active(&self, row: u32, col: u32) -> bool {
+        row < self.rows && col < self.cols && self.active[self.index(row, col)] != 0
+    }
+
+    /// Marks a single macroblock as active or inactive.
+    pub fn set_active(&mut self, row: u32, col: u32, active: bool) -> Result<()> {
+        if row >= self.rows || col >= self.cols {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "VP8E_SET_ACTIVEMAP",
+                format!("macroblock ({}, {}) is outside of the {}x{} map",
+                        row, col, self.rows, self.cols)));
+        }
+        let index = self.index(row, col);
+        self.active[index] = active as u8;
+        Ok(())
+    }
+
+    /// Marks all macroblocks which overlap with the given rectangle (in pixels) as active or
+    /// inactive. The part of the rectangle which is outside of the frame is ignored.
+    pub fn set_region(&mut self, x: u32, y: u32, width: u32, height: u32, active: bool) {
+        let size = ACTIVE_MAP_BLOCK_SIZE;
+        let first_row = cmp::min(y / size, self.rows);
+        let first_col = cmp::min(x / size, self.cols);
+        let last_row = cmp::min(blocks(y as u64 + height as u64, size), self.rows as u64) as u32;
+        let last_col = cmp::min(blocks(x as u64 + width as u64, size), self.cols as u64) as u32;
+        for row in first_row..last_row {
+            let start = row as usize * self.cols as usize;
+            for block in &mut self.active[start + first_col as usize..start + last_col as usize] {
+                *block = active as u8;
+            }
+        }
+    }
+
+    fn index(&self, row: u32, col: u32) -> usize {
+        row as usize * self.cols as usize + col as usize
+    }
+}
+
+impl<Enc: VpxEncoder> Encoder<Enc> {
+    /// Creates an active map (with all macroblocks active) sized for the configured frame
+    /// dimensions of the encoder.
+    pub fn new_active_map(&self) -> ActiveMap {
+        ActiveMap::new(self.config.g_w, self.config.g_h)
+    }
+
+    /// Sets the active map which is used for the frames encoded next (`VP8E_SET_ACTIVEMAP`). The
+    /// map has to match the configured frame dimensions.
+    pub fn set_active_map(&mut self, map: &ActiveMap) -> Result<()> {
+        self.check_active_map(map, "VP8E_SET_ACTIVEMAP")?;
+        let mut active_map = ffi::vpx_active_map_t {
+            active_map: map.active.as_ptr() as *mut u8,
+            rows: map.rows,
+            cols: map.cols,
+        };
+        self.control_ptr(ffi::vp8e_enc_control_id::VP8E_SET_ACTIVEMAP,
+                         &mut active_map,
+                         "VP8E_SET_ACTIVEMAP")
+    }
+
+    /// Disables the active map, so all macroblocks are encoded again.
+    pub fn clear_active_map(&mut self) -> Result<()> {
+        let expected = self.new_active_map();
+        let mut active_map = ffi::vpx_active_map_t {
+            active_map: null_mut(),
+            rows: expected.rows,
+            cols: expected.cols,
+        };
+        self.control_ptr(ffi::vp8e_enc_control_id::VP8E_SET_ACTIVEMAP,
+                         &mut active_map,
+                         "VP8E_SET_ACTIVEMAP")
+    }
+
+    fn check_active_map(&self, map: &ActiveMap, operation: &'static str) -> Result<()> {
+        let expected = self.new_active_map();
+        if (map.rows, map.cols) != (expected.rows, expected.cols) {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                operation,
+                format!("{}x{} map does not match the {}x{} macroblocks of the frame",
+                        map.rows, map.cols, expected.rows, expected.cols)));
+        }
+        Ok(())
+    }
+}
+
+impl Encoder<VP9> {
+    /// Returns the active map which is currently used by the encoder (`VP9E_GET_ACTIVEMAP`).
+    pub fn active_map(&mut self) -> Result<ActiveMap> {
+        let mut map = self.new_active_map();
+        {
+            let mut active_map = ffi::vpx_active_map_t {
+                active_map: map.active.as_mut_ptr(),
+                rows: map.rows,
+                cols: map.cols,
+            };
+            self.control_ptr(ffi::vp8e_enc_control_id::VP9E_GET_ACTIVEMAP,
+                             &mut active_map,
+                             "VP9E_GET_ACTIVEMAP")?;
+        }
+        Ok(map)
+    }
+}
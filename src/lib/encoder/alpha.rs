@@ -0,0 +1,312 @@
+//! Encoding of the images with an alpha channel the way WebM stores them: as a color stream and
+//! an alpha stream which carries the alpha plane as its luma.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::collections::vec_deque::Drain;
+
+use ffi;
+
+use encoder::{CodecFlags, Deadline, Encoder, EncoderConfig, PacketKind, VpxEncoder};
+use error::{CodecError, Error, VPXResult as Result};
+use image::{Format, Image};
+
+/// A pair of the encoded color and alpha frames with the same timestamp. In WebM the color frame
+/// is stored as the block itself and the alpha frame as its `BlockAdditional` with the
+/// `BlockAddID` of 1.
+#[derive(Clone, Debug)]
+pub struct AlphaFrame {
+    pts: ffi::vpx_codec_pts_t,
+    duration: u64,
+    keyframe: bool,
+    color: Vec<u8>,
+    alpha: Vec<u8>,
+}
+
+impl AlphaFrame {
+    pub fn pts(&self) -> ffi::vpx_codec_pts_t {
+        self.pts
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.duration
+    }
+
+    /// Whether both frames are keyframes (the keyframes of the streams are synchronized).
+    pub fn is_keyframe(&self) -> bool {
+        self.keyframe
+    }
+
+    pub fn color(&self) -> &[u8] {
+        &self.color
+    }
+
+    /// The alpha frame, empty if it could not be encoded (see `AlphaEncoder::encode()`).
+    pub fn alpha(&self) -> &[u8] {
+        &self.alpha
+    }
+}
+
+/// Encodes the images with an alpha channel using two encoders of the same codec, since libvpx
+/// cannot encode the alpha channel directly. The alpha encoder follows the color one: it encodes
+/// a frame only if the color encoder has produced one and makes a keyframe whenever the color
+/// encoder does, so that the streams can be decoded side by side (see `AlphaDecoder`).
+pub struct AlphaEncoder<Enc: VpxEncoder> {
+    color: Encoder<Enc>,
+    alpha: Encoder<Enc>,
+    pending: VecDeque<AlphaFrame>,
+    // Whether the next alpha frame has to be a keyframe, since an alpha frame is missing.
+    alpha_keyframe: bool,
+}
+
+impl<Enc: VpxEncoder> AlphaEncoder<Enc> {
+    /// Creates the encoders of the color and the alpha streams, the configurations must have the
+    /// same frame dimensions. The alpha samples have 8 bits, so the alpha configuration has to be
+    /// an 8-bit one (e.g. the profile 0) even if the color stream uses the profile 2 or 3; the
+    /// alpha encoder is created without `CodecFlags::highbitdepth()`. The frame lag is set to 0
+    /// for both encoders, and the alpha encoder neither drops frames nor places keyframes on its
+    /// own.
+    pub fn new(mut config: EncoderConfig<Enc>,
+               mut alpha_config: EncoderConfig<Enc>,
+               flags: Option<CodecFlags>)
+        -> Result<Self>
+    {
+        let violation = if config.frame_dimensions() != alpha_config.frame_dimensions() {
+            Some("the color and the alpha streams must have the same dimensions".to_string())
+        } else if alpha_config.bit_depth() != 8 {
+            Some(format!("the alpha stream must have 8 bits, not {}", alpha_config.bit_depth()))
+        } else {
+            None
+        };
+        if let Some(detail) = violation {
+            return Err(Error::with_detail(CodecError::InvalidParam, "AlphaEncoder::new", detail));
+        }
+
+        let mut alpha_flags = flags.unwrap_or(CodecFlags::default());
+        alpha_flags.highbitdepth(false);
+
+        config.set_lag_in_frames(0);
+        alpha_config.set_lag_in_frames(0);
+        alpha_config.set_drop_frame_threshold(0);
+        alpha_config.set_auto_keyframes(false);
+        Ok(AlphaEncoder {
+            color: Encoder::new(Some(config), flags)?,
+            alpha: Encoder::new(Some(alpha_config), Some(alpha_flags))?,
+            pending: VecDeque::new(),
+            alpha_keyframe: false,
+        })
+    }
+
+    /// The encoder of the color stream, e.g. to apply the codec controls.
+    pub fn color_encoder(&mut self) -> &mut Encoder<Enc> {
+        &mut self.color
+    }
+
+    /// The encoder of the alpha stream.
+    pub fn alpha_encoder(&mut self) -> &mut Encoder<Enc> {
+        &mut self.alpha
+    }
+
+    /// Encodes the color image and its alpha plane (8-bit samples, one per pixel, `alpha_stride`
+    /// bytes per row). The encoded pairs can be obtained with `frames_iter()` afterwards. If only
+    /// the alpha frame fails, the error is returned, but the color frame is still produced
+    /// without alpha and the alpha stream continues with a keyframe.
+    pub fn encode(&mut self,
+                  image: &Image,
+                  alpha: &[u8],
+                  alpha_stride: u32,
+                  pts: ffi::vpx_codec_pts_t,
+                  duration: u64,
+                  flags: Enc::FrameFlags,
+                  deadline: Deadline)
+        -> Result<()>
+    {
+        let alpha_image = alpha_image(alpha, alpha_stride, image)?;
+        // The same flags are applied to both streams.
+        let flags: ffi::vpx_enc_frame_flags_t = flags.into();
+
+        self.color.encode_raw(image, pts, duration, flags, deadline.clone())?;
+        let color = match take_frame(&mut self.color) {
+            Some(frame) => frame,
+            // The frame has been dropped by the rate control.
+            None => return Ok(()),
+        };
+
+        let mut alpha_flags = flags;
+        if color.keyframe || self.alpha_keyframe {
+            alpha_flags |= ffi::VPX_EFLAG_FORCE_KF as i64;
+        }
+        let encoded = self.alpha.encode_raw(&alpha_image, pts, duration, alpha_flags, deadline);
+        let alpha = match encoded {
+            Ok(()) => take_frame(&mut self.alpha).ok_or_else(|| {
+                Error::with_detail(CodecError::Unspecified,
+                                   "vpx_codec_encode",
+                                   format!("no alpha frame has been produced for {}", pts))
+            }),
+            Err(error) => Err(error),
+        };
+
+        match alpha {
+            Ok(alpha) => {
+                self.alpha_keyframe = false;
+                self.pending.push_back(AlphaFrame {
+                    alpha: alpha.color,
+                    ..color
+                });
+                Ok(())
+            },
+            Err(error) => {
+                self.alpha_keyframe = true;
+                self.pending.push_back(color);
+                Err(error)
+            },
+        }
+    }
+
+    /// Returns the pairs of the frames produced by the `encode()` calls so far.
+    pub fn frames_iter(&mut self) -> Drain<AlphaFrame> {
+        self.pending.drain(..)
+    }
+}
+
+/// Copies the alpha plane into the luma of a gray I420 image with the dimensions of `image`.
+fn alpha_image(alpha: &[u8], stride: u32, image: &Image) -> Result<Image<'static>> {
+    let width = image.d_w;
+    let height = image.d_h;
+    let required = (stride as usize).checked_mul(height.saturating_sub(1) as usize)
+                                    .and_then(|size| size.checked_add(width as usize));
+    if width == 0 || height == 0 || stride < width ||
+       required.map_or(true, |required| alpha.len() < required) {
+        return Err(Error::with_detail(
+            CodecError::InvalidParam,
+            "AlphaEncoder::encode",
+            format!("the alpha plane is too small for {}x{}", width, height)));
+    }
+
+    // Same layout as `vpx_img_wrap()` produces with the stride alignment of 2.
+    let luma_stride = (width as usize + 1) & !1;
+    let aligned_height = (height as usize + 1) & !1;
+    let luma_size = luma_stride * aligned_height;
+    let mut data = vec![128u8; luma_size + luma_size / 2];
+    let rows = data.chunks_mut(luma_stride).zip(alpha.chunks(stride as usize));
+    for (dst, src) in rows.take(height as usize) {
+        let width = width as usize;
+        dst[..width].copy_from_slice(&src[..width]);
+    }
+
+    Ok(Image::new(Cow::Owned(data),
+                  Format::I420 { hi_bit_depth: false },
+                  image.color_space(),
+                  width,
+                  height,
+                  2))
+}
+
+/// Returns the frame the encoder has produced for the last encoded image, if any. The encoder has
+/// to be configured without the frame lag.
+fn take_frame<Enc: VpxEncoder>(encoder: &mut Encoder<Enc>) -> Option<AlphaFrame> {
+    let mut result: Option<AlphaFrame> = None;
+    for packet in encoder.frames_iter() {
+        if let PacketKind::EncodedFrame(frame) = packet {
+            let joined = result.get_or_insert_with(|| AlphaFrame {
+                pts: frame.pts(),
+                duration: frame.duration(),
+                keyframe: false,
+                color: Vec::new(),
+                alpha: Vec::new(),
+            });
+            // The partitions of a VP8 frame are joined together.
+            joined.color.extend_from_slice(frame.data());
+            joined.keyframe |= frame.is_keyframe();
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::slice;
+
+    use decoder::Decoder;
+    use encoder::{CodecFlags, Deadline, Encoder, EncoderConfig, PacketKind};
+    use encoder::vp8::{FrameFlags, VP8};
+    use image::{ColorSpace, Format, Image};
+    use super::{alpha_image, take_frame};
+
+    const WIDTH: u32 = 64;
+    const HEIGHT: u32 = 48;
+
+    fn i420_image(data: &[u8], width: u32, height: u32) -> Image {
+        Image::new(Cow::Borrowed(data),
+                   Format::I420 { hi_bit_depth: false },
+                   ColorSpace::BT601,
+                   width,
+                   height,
+                   1)
+    }
+
+    fn plane_rows(image: &Image, plane: usize, width: usize, height: usize) -> Vec<Vec<u8>> {
+        let stride = image.stride[plane] as usize;
+        let data = unsafe { slice::from_raw_parts(image.planes[plane], stride * height) };
+        data.chunks(stride).map(|row| row[..width].to_vec()).collect()
+    }
+
+    fn encoder(output_partition: bool) -> Encoder<VP8> {
+        let mut config = EncoderConfig::<VP8>::new().unwrap();
+        config.set_frame_dimensions(WIDTH, HEIGHT);
+        config.set_timebase(1, 30);
+        config.set_lag_in_frames(0);
+        let mut flags = CodecFlags::default();
+        flags.output_partition(output_partition);
+        Encoder::new(Some(config), Some(flags)).unwrap()
+    }
+
+    #[test]
+    fn alpha_rows_follow_the_stride() {
+        let color = vec![0u8; 24];
+        let image = i420_image(&color, 3, 3);
+        // Two bytes of padding at the end of each row, none after the last one.
+        let alpha: Vec<u8> = (0..13).collect();
+
+        let gray = alpha_image(&alpha, 5, &image).unwrap();
+        assert_eq!((gray.d_w, gray.d_h), (3, 3));
+        assert_eq!(plane_rows(&gray, 0, 3, 3),
+                   vec![vec![0, 1, 2], vec![5, 6, 7], vec![10, 11, 12]]);
+        for plane in 1..3 {
+            assert_eq!(plane_rows(&gray, plane, 2, 2), vec![vec![128, 128], vec![128, 128]]);
+        }
+
+        assert!(alpha_image(&alpha[..12], 5, &image).is_err());
+        assert!(alpha_image(&alpha, 2, &image).is_err());
+    }
+
+    #[test]
+    fn partitions_are_joined() {
+        let data: Vec<u8> = (0..WIDTH * HEIGHT * 3 / 2).map(|i| (i * 7) as u8).collect();
+        let image = i420_image(&data, WIDTH, HEIGHT);
+
+        let mut partitions = Vec::new();
+        {
+            let mut encoder = encoder(true);
+            encoder.encode(&image, 0, 1, FrameFlags::default(), Deadline::GoodQuality).unwrap();
+            for packet in encoder.frames_iter() {
+                if let PacketKind::EncodedFrame(frame) = packet {
+                    partitions.push(frame.data().to_vec());
+                }
+            }
+        }
+        assert!(partitions.len() > 1);
+
+        let mut encoder = encoder(true);
+        encoder.encode(&image, 0, 1, FrameFlags::default(), Deadline::GoodQuality).unwrap();
+        let frame = take_frame(&mut encoder).unwrap();
+        assert_eq!((frame.pts, frame.keyframe), (0, true));
+        assert_eq!(frame.color, partitions.concat());
+
+        let mut decoder = Decoder::<VP8>::new().unwrap();
+        decoder.decode(&frame.color).unwrap();
+        let decoded = decoder.frames_iter().next().expect("No decoded image");
+        assert_eq!((decoded.width(), decoded.height()), (WIDTH, HEIGHT));
+    }
+}
@@ -4,30 +4,114 @@ use std::marker::PhantomData;
 
 use ffi;
 
-use encoder::VpxEncoder;
-use error::{VPXResult as Result, check_err};
+use capabilities::Capabilities;
+use encoder::{ContentType, LayerId, TemporalLayering, Vp9Level, VpxEncoder};
+use encoder::vp9::VP9;
+use error::{CodecError, Error, VPXResult as Result, check_err};
 
-/// An encoder configuration for a specific codec `Encoder`.
+/// An encoder configuration for a specific codec `Encoder`. Besides the libvpx configuration
+/// structure it may contain the settings which are applied as codec controls right after the
+/// encoder has been created.
 pub struct EncoderConfig<Encoder> {
     inner: ffi::vpx_codec_enc_cfg_t,
+    cpu_used: Option<i32>,
+    content_type: Option<ContentType>,
+    frame_rate: Option<f64>,
+    target_level: Option<Vp9Level>,
     _phantom: PhantomData<Encoder>,
 }
 
+/// Rate control mode of the encoder.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RateControl {
+    /// Variable bitrate.
+    VBR,
+    /// Constant bitrate, suits the realtime communication.
+    CBR,
+    /// Constrained quality.
+    CQ,
+    /// Constant quality.
+    Q,
+}
+
+impl Into<ffi::vpx_rc_mode> for RateControl {
+    fn into(self) -> ffi::vpx_rc_mode {
+        match self {
+            RateControl::VBR => ffi::vpx_rc_mode::VPX_VBR,
+            RateControl::CBR => ffi::vpx_rc_mode::VPX_CBR,
+            RateControl::CQ => ffi::vpx_rc_mode::VPX_CQ,
+            RateControl::Q => ffi::vpx_rc_mode::VPX_Q,
+        }
+    }
+}
+
+/// The number of bits per sample the encoder works with.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BitDepth {
+    Eight,
+    Ten,
+    Twelve,
+}
+
+impl BitDepth {
+    pub fn bits(&self) -> u32 {
+        match *self {
+            BitDepth::Eight => 8,
+            BitDepth::Ten => 10,
+            BitDepth::Twelve => 12,
+        }
+    }
+}
+
+impl Into<ffi::vpx_bit_depth_t> for BitDepth {
+    fn into(self) -> ffi::vpx_bit_depth_t {
+        match self {
+            BitDepth::Eight => ffi::vpx_bit_depth::VPX_BITS_8,
+            BitDepth::Ten => ffi::vpx_bit_depth::VPX_BITS_10,
+            BitDepth::Twelve => ffi::vpx_bit_depth::VPX_BITS_12,
+        }
+    }
+}
+
 impl<Encoder: VpxEncoder> EncoderConfig<Encoder> {
     pub fn new() -> Result<Self> {
         let mut cfg: ffi::vpx_codec_enc_cfg_t = Default::default();
-        check_err(unsafe { ffi::vpx_codec_enc_config_default(Encoder::interface(), &mut cfg, 0) })?;
+        check_err(unsafe { ffi::vpx_codec_enc_config_default(Encoder::interface(), &mut cfg, 0) },
+                  "vpx_codec_enc_config_default")?;
         Ok(EncoderConfig {
             inner: cfg,
+            cpu_used: None,
+            content_type: None,
+            frame_rate: None,
+            target_level: None,
             _phantom: PhantomData { }
         })
     }
 
+    /// Creates a configuration for the screen sharing: constant bitrate, no frame lag, the encoder
+    /// tuned for the screen content and a fast `cpu_used` setting.
+    ///
+    /// The deadline is not a part of the configuration: callers must pass `Deadline::Realtime` to
+    /// `Encoder::encode()` themselves, otherwise the fast `cpu_used` setting has no effect and the
+    /// encoding is much slower than expected.
+    pub fn screen_share() -> Result<Self> {
+        let mut config = Self::new()?;
+        config.set_rate_control(RateControl::CBR);
+        config.set_lag_in_frames(0);
+        config.set_cpu_used(Encoder::screen_share_cpu_used());
+        config.set_content_type(ContentType::Screen);
+        Ok(config)
+    }
+
     pub fn set_frame_dimensions(&mut self, width: u32, height: u32) {
         self.inner.g_w = width;
         self.inner.g_h = height;
     }
 
+    pub fn frame_dimensions(&self) -> (u32, u32) {
+        (self.inner.g_w, self.inner.g_h)
+    }
+
     /// Set the timebase.
     ///
     /// > **EXAMPLE**: if you want to write a simple encoder which encodes frames
@@ -40,9 +124,240 @@ impl<Encoder: VpxEncoder> EncoderConfig<Encoder> {
         self.inner.g_timebase.den = denominator as i32;
     }
 
+    /// Set the maximum frame rate of the input. It is only used to validate the configuration
+    /// against the target level, the timing of the frames is defined by their timestamps.
+    pub fn set_frame_rate(&mut self, frame_rate: f64) {
+        self.frame_rate = Some(frame_rate);
+    }
+
+    /// The frame rate set with `set_frame_rate()`, if any.
+    pub fn frame_rate(&self) -> Option<f64> {
+        self.frame_rate
+    }
+
     pub fn set_target_bitrate(&mut self, bitrate: u32) {
         self.inner.rc_target_bitrate = bitrate;
     }
+
+    pub fn target_bitrate(&self) -> u32 {
+        self.inner.rc_target_bitrate
+    }
+
+    pub fn set_rate_control(&mut self, mode: RateControl) {
+        self.inner.rc_end_usage = mode.into();
+    }
+
+    /// Set the speed/quality trade-off which is applied once the encoder is created, see
+    /// `Encoder::set_cpu_used()`.
+    pub fn set_cpu_used(&mut self, cpu_used: i32) {
+        self.cpu_used = Some(cpu_used);
+    }
+
+    pub fn cpu_used(&self) -> Option<i32> {
+        self.cpu_used
+    }
+
+    /// Set the type of the content which is applied once the encoder is created, see
+    /// `Encoder::set_content_type()`.
+    pub fn set_content_type(&mut self, content_type: ContentType) {
+        self.content_type = Some(content_type);
+    }
+
+    pub fn content_type(&self) -> Option<ContentType> {
+        self.content_type
+    }
+
+    pub fn target_level(&self) -> Option<Vp9Level> {
+        self.target_level
+    }
+
+    /// Set the number of frames the encoder may look ahead (and delay the output by). `0` makes the
+    /// encoder output each frame right after it has been encoded.
+    pub fn set_lag_in_frames(&mut self, frames: u32) {
+        self.inner.g_lag_in_frames = frames;
+    }
+
+    pub fn profile(&self) -> u32 {
+        self.inner.g_profile
+    }
+
+    /// The number of bits per sample of the encoded stream.
+    pub fn bit_depth(&self) -> u32 {
+        self.inner.g_bit_depth as u32
+    }
+
+    /// The number of bits per sample of the input images.
+    pub fn input_bit_depth(&self) -> u32 {
+        self.inner.g_input_bit_depth
+    }
+
+    /// Checks that the profile and the bit depths are consistent with each other and with the
+    /// flags the encoder is created with: the profiles 0 and 1 are 8-bit, the profiles 2 and 3
+    /// need a higher bit depth and `CodecFlags::highbitdepth()`.
+    pub fn validate_bit_depth(&self, flags: &CodecFlags) -> Result<()> {
+        let profile = self.profile();
+        let bit_depth = self.bit_depth();
+        let input_bit_depth = self.input_bit_depth();
+        let violation = if profile > 3 {
+            Some(format!("invalid profile {}", profile))
+        } else if (profile >= 2) != (bit_depth > 8) {
+            Some(format!("the profile {} cannot be used with {} bits", profile, bit_depth))
+        } else if input_bit_depth > bit_depth {
+            Some(format!("the input bit depth {} exceeds the bit depth {}",
+                         input_bit_depth, bit_depth))
+        } else if bit_depth > 8 && !flags.use_highbitdepth {
+            Some(format!("{} bits need the high bit-depth flag", bit_depth))
+        } else {
+            None
+        };
+
+        match violation {
+            Some(detail) => Err(Error::with_detail(CodecError::InvalidParam,
+                                                   "vpx_codec_enc_init",
+                                                   detail)),
+            None => Ok(()),
+        }
+    }
+
+    /// Set the buffer level (in percent) below which the encoder drops frames to keep the
+    /// bitrate, `0` disables the frame dropping.
+    pub fn set_drop_frame_threshold(&mut self, threshold: u32) {
+        self.inner.rc_dropframe_thresh = threshold;
+    }
+
+    /// Enable or disable the keyframes placed by the encoder itself (e.g. on scene changes or
+    /// after the maximum keyframe interval). If disabled, the keyframes are only produced when
+    /// requested with the frame flags.
+    pub fn set_auto_keyframes(&mut self, enabled: bool) {
+        self.inner.kf_mode = if enabled {
+            ffi::vpx_kf_mode::VPX_KF_AUTO
+        } else {
+            ffi::vpx_kf_mode::VPX_KF_DISABLED
+        };
+    }
+
+    /// Set the maximum number of threads the encoder may use. VP9 needs tile columns (or the row
+    /// based multithreading) to actually encode a frame in parallel.
+    pub fn set_threads(&mut self, threads: u32) {
+        self.inner.g_threads = threads;
+    }
+
+    /// Configures the temporal layers according to the given pattern. The bitrate of each layer
+    /// is derived from the target bitrate, so it has to be set before calling this function.
+    pub fn set_temporal_layering(&mut self, layering: &TemporalLayering<Encoder>) {
+        let bitrates = layering.layer_bitrates(self.inner.rc_target_bitrate);
+        self.inner.ts_number_layers = layering.number_of_layers();
+        self.inner.ts_periodicity = layering.periodicity();
+        for (i, id) in layering.layer_ids().iter().enumerate() {
+            self.inner.ts_layer_id[i] = *id;
+        }
+        for (i, decimator) in layering.rate_decimators().iter().enumerate() {
+            self.inner.ts_rate_decimator[i] = *decimator;
+        }
+        for (i, bitrate) in bitrates.iter().enumerate() {
+            self.inner.ts_target_bitrate[i] = *bitrate;
+            self.inner.layer_target_bitrate[i] = *bitrate;
+        }
+        if layering.number_of_layers() > 1 {
+            // Layer ids and frame flags are provided by the application.
+            self.inner.temporal_layering_mode =
+                ffi::vp9e_temporal_layering_mode::VP9E_TEMPORAL_LAYERING_MODE_BYPASS as i32;
+        }
+    }
+}
+
+impl EncoderConfig<VP9> {
+    /// Set the level the stream is constrained to. The configuration is validated against the
+    /// limits of the level (see `Vp9Level::validate()`) when the encoder is created, so the frame
+    /// dimensions, the frame rate and the bitrate should be set as well. The sample rate of the
+    /// level is only checked if the frame rate has been set.
+    pub fn set_target_level(&mut self, level: Vp9Level) {
+        self.target_level = Some(level);
+    }
+
+    /// Creates a configuration for the lossless encoding: the quantizer is fixed to 0. The
+    /// encoder has to be switched to the lossless mode with `Encoder::set_lossless()` as well.
+    pub fn lossless() -> Result<Self> {
+        let mut config = Self::new()?;
+        config.inner.rc_min_quantizer = 0;
+        config.inner.rc_max_quantizer = 0;
+        Ok(config)
+    }
+
+    /// Creates a configuration for the profile 2, i.e. the high bit-depth 4:2:0 encoding. The
+    /// input images have to be in `Format::I420 { hi_bit_depth: true }` with the same bit depth
+    /// and the encoder has to be created with `CodecFlags::highbitdepth()`.
+    pub fn profile_2(bit_depth: BitDepth) -> Result<Self> {
+        Self::high_bit_depth(2, bit_depth)
+    }
+
+    /// Creates a configuration for the profile 3, i.e. the high bit-depth 4:2:2, 4:4:0 or 4:4:4
+    /// encoding. See `profile_2()`.
+    pub fn profile_3(bit_depth: BitDepth) -> Result<Self> {
+        Self::high_bit_depth(3, bit_depth)
+    }
+
+    fn high_bit_depth(profile: u32, bit_depth: BitDepth) -> Result<Self> {
+        if bit_depth == BitDepth::Eight {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "vpx_codec_enc_config_default",
+                format!("the profile {} needs more than 8 bits", profile)));
+        }
+        let mut config = Self::new()?;
+        config.set_profile(profile);
+        config.set_bit_depth(bit_depth);
+        config.set_input_bit_depth(bit_depth.bits());
+        Ok(config)
+    }
+
+    /// Set the VP9 profile: 0 for the 8-bit 4:2:0, 1 for the 8-bit 4:2:2/4:4:0/4:4:4, 2 and 3
+    /// for the same with 10 or 12 bits.
+    pub fn set_profile(&mut self, profile: u32) {
+        self.inner.g_profile = profile;
+    }
+
+    /// Set the number of bits per sample of the encoded stream.
+    pub fn set_bit_depth(&mut self, bit_depth: BitDepth) {
+        self.inner.g_bit_depth = bit_depth.into();
+    }
+
+    /// Set the number of bits per sample of the input images, which may be lower than the bit
+    /// depth of the stream.
+    pub fn set_input_bit_depth(&mut self, bits: u32) {
+        self.inner.g_input_bit_depth = bits;
+    }
+
+    /// Sets the number of spatial layers of the SVC encoder. The layers are configured with
+    /// `Encoder::set_svc_parameters()` once the encoder has been created.
+    pub fn set_spatial_layers(&mut self, layers: u32) {
+        self.inner.ss_number_layers = layers;
+    }
+
+    /// Switches the layering to the bypass mode, in which the application provides the layer ids
+    /// and the reference structure of each frame (see `Encoder::set_svc_ref_frame_config()`).
+    /// `set_temporal_layering()` does it for more than one temporal layer, the spatial-only SVC
+    /// needs to call this.
+    pub fn set_bypass_layering(&mut self) {
+        self.inner.temporal_layering_mode =
+            ffi::vp9e_temporal_layering_mode::VP9E_TEMPORAL_LAYERING_MODE_BYPASS as i32;
+    }
+
+    /// Sets the target bitrate (in kbps) of the given layer. The bitrates are cumulative, i.e.
+    /// each layer includes the bitrate of the layers it depends on. The number of the temporal
+    /// layers has to be configured before.
+    pub fn set_layer_target_bitrate(&mut self, layer: LayerId, bitrate: u32) -> Result<()> {
+        let temporal_layers = self.inner.ts_number_layers.max(1);
+        let index = (layer.spatial * temporal_layers + layer.temporal) as usize;
+        if layer.temporal >= temporal_layers || index >= self.inner.layer_target_bitrate.len() {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "EncoderConfig::set_layer_target_bitrate",
+                format!("layer {:?} is out of range", layer)));
+        }
+        self.inner.layer_target_bitrate[index] = bitrate;
+        Ok(())
+    }
 }
 
 impl<Encoder> Into<ffi::vpx_codec_enc_cfg_t> for EncoderConfig<Encoder> {
@@ -62,6 +377,76 @@ pub struct CodecFlags {
     use_frame_threading: bool,
 }
 
+impl CodecFlags {
+    /// Calculate and report the PSNR of the encoded frames (encoder only).
+    pub fn psnr(&mut self, enable: bool) {
+        self.use_psnr = enable;
+    }
+
+    /// Make the encoder output one partition at a time (encoder only).
+    pub fn output_partition(&mut self, enable: bool) {
+        self.use_output_partition = enable;
+    }
+
+    /// Use high bit-depth internally (encoder only).
+    pub fn highbitdepth(&mut self, enable: bool) {
+        self.use_highbitdepth = enable;
+    }
+
+    /// Postprocess the decoded frames (decoder only).
+    pub fn postproc(&mut self, enable: bool) {
+        self.use_postproc = enable;
+    }
+
+    /// Conceal the errors in the decoded frames (decoder only).
+    pub fn error_concealment(&mut self, enable: bool) {
+        self.use_error_concealment = enable;
+    }
+
+    /// The input frames are passed to the decoder in fragments (decoder only).
+    pub fn input_fragments(&mut self, enable: bool) {
+        self.use_input_fragments = enable;
+    }
+
+    /// Use frame-based multithreading in the decoder (decoder only).
+    pub fn frame_threading(&mut self, enable: bool) {
+        self.use_frame_threading = enable;
+    }
+
+    /// Checks that the flags can be used to initialize an encoder with the given capabilities.
+    /// libvpx only reports `CodecError::Incapable` in such cases, and the bit values of the
+    /// decoder flags overlap with the encoder ones, so the decoder-only flags are rejected here.
+    pub fn validate_for_encoder(&self, caps: &Capabilities) -> Result<()> {
+        let decoder_flags = [(self.use_postproc, "postproc"),
+                             (self.use_error_concealment, "error concealment"),
+                             (self.use_input_fragments, "input fragments"),
+                             (self.use_frame_threading, "frame threading")];
+        if let Some(&(_, name)) = decoder_flags.iter().find(|&&(requested, _)| requested) {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "vpx_codec_enc_init",
+                format!("the {} flag can only be used with a decoder", name)));
+        }
+
+        let encoder_flags = [
+            (self.use_psnr, caps.psnr(), "PSNR"),
+            (self.use_output_partition, caps.output_partition(), "output partition"),
+            (self.use_highbitdepth, caps.highbitdepth(), "high bit-depth"),
+        ];
+        let unsupported = encoder_flags.iter().find(|&&(requested, supported, _)| {
+            requested && !supported
+        });
+        if let Some(&(_, _, name)) = unsupported {
+            return Err(Error::with_detail(
+                CodecError::Incapable,
+                "vpx_codec_enc_init",
+                format!("the encoder does not support the {} flag", name)));
+        }
+
+        Ok(())
+    }
+}
+
 impl Into<ffi::vpx_codec_flags_t> for CodecFlags {
     fn into(self) -> ffi::vpx_codec_flags_t {
         let mut flags: ffi::vpx_codec_flags_t = 0;
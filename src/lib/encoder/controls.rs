@@ -0,0 +1,175 @@
+//! Encoder controls (`vpx_codec_control()`) which are common for VP8 and VP9 encoders.
+
+use ffi;
+
+use encoder::{Encoder, VpxEncoder};
+use error::{CodecError, Error, VPXResult as Result};
+
+/// The metric the encoder optimizes the quality for.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Tuning {
+    Psnr,
+    Ssim,
+}
+
+impl Into<ffi::vp8e_tuning> for Tuning {
+    fn into(self) -> ffi::vp8e_tuning {
+        match self {
+            Tuning::Psnr => ffi::vp8e_tuning::VP8_TUNE_PSNR,
+            Tuning::Ssim => ffi::vp8e_tuning::VP8_TUNE_SSIM,
+        }
+    }
+}
+
+/// The type of the content being encoded, which the encoder is tuned for.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ContentType {
+    Default,
+    /// Screen captures: sharp text and large static areas.
+    Screen,
+    /// Film grain content, only supported by VP9.
+    Film,
+}
+
+/// Internal scaling of the frame along one axis.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Scaling {
+    Normal,
+    FourFifths,
+    ThreeFifths,
+    OneHalf,
+}
+
+impl Scaling {
+    /// Returns the scaling ratio as `(numerator, denominator)`.
+    pub fn ratio(&self) -> (u32, u32) {
+        match *self {
+            Scaling::Normal => (1, 1),
+            Scaling::FourFifths => (4, 5),
+            Scaling::ThreeFifths => (3, 5),
+            Scaling::OneHalf => (1, 2),
+        }
+    }
+
+    /// Returns the scaled size, rounded up the same way libvpx does it.
+    pub fn apply(&self, size: u32) -> u32 {
+        let (num, den) = self.ratio();
+        (den - 1 + size * num) / den
+    }
+}
+
+impl Default for Scaling {
+    fn default() -> Self {
+        Scaling::Normal
+    }
+}
+
+impl Into<ffi::vpx_scaling_mode_1d> for Scaling {
+    fn into(self) -> ffi::vpx_scaling_mode_1d {
+        match self {
+            Scaling::Normal => ffi::vpx_scaling_mode_1d::VP8E_NORMAL,
+            Scaling::FourFifths => ffi::vpx_scaling_mode_1d::VP8E_FOURFIVE,
+            Scaling::ThreeFifths => ffi::vpx_scaling_mode_1d::VP8E_THREEFIVE,
+            Scaling::OneHalf => ffi::vpx_scaling_mode_1d::VP8E_ONETWO,
+        }
+    }
+}
+
+/// Internal scaling of the frames, which reduces the coded resolution without reinitializing
+/// the encoder. The decoder reports the scaled size, it is up to the player to scale the frames
+/// back.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ScalingMode {
+    pub horizontal: Scaling,
+    pub vertical: Scaling,
+}
+
+impl ScalingMode {
+    /// Returns the coded size of a frame of the given dimensions.
+    pub fn apply(&self, width: u32, height: u32) -> (u32, u32) {
+        (self.horizontal.apply(width), self.vertical.apply(height))
+    }
+}
+
+impl<Enc: VpxEncoder> Encoder<Enc> {
+    /// Sets the speed/quality trade-off (`VP8E_SET_CPUUSED`). Higher values make the encoding
+    /// faster at the cost of quality, negative values are the same as the positive ones but let
+    /// the encoder adjust the speed to meet the deadline. The range is -16..16 for VP8 and -8..8
+    /// for VP9; values above 4 (VP8) or 5 (VP9) are only useful with `Deadline::Realtime`.
+    pub fn set_cpu_used(&mut self, cpu_used: i32) -> Result<()> {
+        let (min, max) = Enc::cpu_used_range();
+        check_range("VP8E_SET_CPUUSED", cpu_used, min, max)?;
+        self.control(ffi::vp8e_enc_control_id::VP8E_SET_CPUUSED, cpu_used, "VP8E_SET_CPUUSED")
+    }
+
+    /// Sets the threshold for the blocks to be treated as static, i.e. encoded without residual
+    /// (`VP8E_SET_STATIC_THRESHOLD`). `0` disables the feature, higher values save CPU and bits
+    /// on static content.
+    pub fn set_static_threshold(&mut self, threshold: u32) -> Result<()> {
+        self.control(ffi::vp8e_enc_control_id::VP8E_SET_STATIC_THRESHOLD,
+                     threshold as i32,
+                     "VP8E_SET_STATIC_THRESHOLD")
+    }
+
+    /// Sets the strength of the temporal denoiser (`VP8E_SET_NOISE_SENSITIVITY`), `0` disables
+    /// it. The range is 0..6 for both codecs, VP9 only applies it in the realtime mode.
+    pub fn set_noise_sensitivity(&mut self, sensitivity: u32) -> Result<()> {
+        check_range("VP8E_SET_NOISE_SENSITIVITY", sensitivity as i32, 0, 6)?;
+        self.control(ffi::vp8e_enc_control_id::VP8E_SET_NOISE_SENSITIVITY,
+                     sensitivity as i32,
+                     "VP8E_SET_NOISE_SENSITIVITY")
+    }
+
+    /// Sets the sharpness of the loop filter (`VP8E_SET_SHARPNESS`) in the range 0..7 for both
+    /// codecs. Higher values preserve more details, but may produce more blocking artifacts.
+    pub fn set_sharpness(&mut self, sharpness: u32) -> Result<()> {
+        check_range("VP8E_SET_SHARPNESS", sharpness as i32, 0, 7)?;
+        self.control(ffi::vp8e_enc_control_id::VP8E_SET_SHARPNESS,
+                     sharpness as i32,
+                     "VP8E_SET_SHARPNESS")
+    }
+
+    /// Tunes the encoder for the given type of content (`VP8E_SET_SCREEN_CONTENT_MODE` for VP8,
+    /// `VP9E_SET_TUNE_CONTENT` for VP9).
+    pub fn set_content_type(&mut self, content_type: ContentType) -> Result<()> {
+        Enc::set_content_type(self, content_type)
+    }
+
+    /// Sets the internal scaling of the frames encoded next (`VP8E_SET_SCALEMODE`). The coded
+    /// size is reported by the frames returned by `frames_iter()`. VP8 only changes the size on
+    /// a keyframe.
+    pub fn set_scaling_mode(&mut self, mode: ScalingMode) -> Result<()> {
+        let mut scaling_mode = ffi::vpx_scaling_mode_t {
+            h_scaling_mode: mode.horizontal.into(),
+            v_scaling_mode: mode.vertical.into(),
+        };
+        self.control_ptr(ffi::vp8e_enc_control_id::VP8E_SET_SCALEMODE,
+                         &mut scaling_mode,
+                         "VP8E_SET_SCALEMODE")?;
+        self.scaling_mode = mode;
+        Ok(())
+    }
+
+    pub fn scaling_mode(&self) -> ScalingMode {
+        self.scaling_mode
+    }
+
+    /// Sets the metric the encoder is tuned for (`VP8E_SET_TUNING`).
+    pub fn set_tuning(&mut self, tuning: Tuning) -> Result<()> {
+        let tuning: ffi::vp8e_tuning = tuning.into();
+        self.control(ffi::vp8e_enc_control_id::VP8E_SET_TUNING,
+                     tuning as i32,
+                     "VP8E_SET_TUNING")
+    }
+}
+
+/// Reports a descriptive error if the value of the control is outside of the given range.
+pub fn check_range(operation: &'static str, value: i32, min: i32, max: i32) -> Result<()> {
+    if value < min || value > max {
+        Err(Error::with_detail(CodecError::InvalidParam,
+                               operation,
+                               format!("{} is out of range {}..{}", value, min, max)))
+    } else {
+        Ok(())
+    }
+}
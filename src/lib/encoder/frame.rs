@@ -1,10 +1,18 @@
-use std::ops::DerefMut;
+use std::collections::VecDeque;
 use std::ptr::null_mut;
 use std::slice;
 
 use ffi;
 
-use context::CodecContext as Context;
+use encoder::EncoderStats;
+use encoder::header::FrameSizeParser;
+
+/// Identifies the spatial and temporal layer of a frame in a scalable stream.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct LayerId {
+    pub spatial: u32,
+    pub temporal: u32,
+}
 
 pub struct Frame<'encoder> {
     data: &'encoder [u8],
@@ -12,6 +20,9 @@ pub struct Frame<'encoder> {
     duration: u64,
     flags: ffi::vpx_codec_frame_flags_t,
     partition_id: i32,
+    layer_id: LayerId,
+    width: u32,
+    height: u32,
 }
 
 impl<'encoder> Frame<'encoder> {
@@ -46,6 +57,35 @@ impl<'encoder> Frame<'encoder> {
     pub fn partition(&self) -> i32 {
         self.partition_id
     }
+
+    /// The temporal layer the frame belongs to, as set with `Encoder::set_temporal_layer_id()`.
+    pub fn temporal_layer_id(&self) -> u32 {
+        self.layer_id.temporal
+    }
+
+    /// The spatial layer the frame belongs to. Only VP9 SVC encoders produce more than one
+    /// spatial layer. The frames of a superframe are numbered by their position, so the id is
+    /// wrong if the superframe contains hidden frames or skips some spatial layers (e.g. when
+    /// the rate control drops a layer).
+    pub fn spatial_layer_id(&self) -> u32 {
+        self.layer_id.spatial
+    }
+
+    pub fn layer_id(&self) -> LayerId {
+        self.layer_id
+    }
+
+    /// The width of the coded frame as parsed from the frame header, which differs from the
+    /// configured one if the encoder scales the frames internally (see
+    /// `Encoder::set_scaling_mode()`) or for the lower VP9 spatial layers.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height of the coded frame, see `width()`.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
 }
 
 pub enum PacketKind<'encoder> {
@@ -58,34 +98,108 @@ pub enum PacketKind<'encoder> {
 
 pub struct FramesIter<'encoder> {
     inner: ffi::vpx_codec_iter_t,
-    codec_ctx: &'encoder mut Context,
+    codec_ctx: &'encoder mut ffi::vpx_codec_ctx_t,
+    layer_id: LayerId,
+    layer_sizes: Vec<(u32, u32)>,
+    pending: VecDeque<Frame<'encoder>>,
+    stats: Option<&'encoder mut EncoderStats>,
+    frame_sizes: Option<&'encoder mut FrameSizeParser>,
 }
 
 impl<'encoder> FramesIter<'encoder> {
-    pub fn new(ctx: &'encoder mut Context) -> Self {
+    /// Creates an iterator over the encoded data. The encoded frames are tagged with the given
+    /// layer id and the coded size of their spatial layer from `layer_sizes`. If there is more
+    /// than one spatial layer, each VP9 superframe is split into the frames it contains, which
+    /// are the spatial layers in case of the VP9 SVC encoder. The frames of a superframe are
+    /// assumed to be consecutive spatial layers, starting with the one of `layer_id`.
+    pub fn new(ctx: &'encoder mut ffi::vpx_codec_ctx_t,
+               layer_id: LayerId,
+               layer_sizes: Vec<(u32, u32)>) -> Self
+    {
         FramesIter {
             inner: null_mut(),
             codec_ctx: ctx,
+            layer_id: layer_id,
+            layer_sizes: layer_sizes,
+            pending: VecDeque::new(),
+            stats: None,
+            frame_sizes: None,
         }
     }
+
+    /// Makes the iterator record the produced frames in the given statistics.
+    pub fn with_stats(mut self, stats: &'encoder mut EncoderStats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Makes the iterator parse the coded size of the frames from their headers, the sizes from
+    /// `layer_sizes` are only used if a header cannot be parsed.
+    pub(crate) fn with_frame_sizes(mut self, frame_sizes: &'encoder mut FrameSizeParser) -> Self {
+        self.frame_sizes = Some(frame_sizes);
+        self
+    }
 }
 
 impl<'encoder> Iterator for FramesIter<'encoder> {
     type Item = PacketKind<'encoder>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let pkt = unsafe { ffi::vpx_codec_get_cx_data(self.codec_ctx.deref_mut(), &mut self.inner) };
+        if let Some(frame) = self.pending.pop_front() {
+            return Some(PacketKind::EncodedFrame(frame));
+        }
+
+        let pkt = unsafe { ffi::vpx_codec_get_cx_data(self.codec_ctx, &mut self.inner) };
         unsafe { pkt.as_ref().map(|pkt_ref| {
             match pkt_ref.kind {
                 ffi::vpx_codec_cx_pkt_kind::VPX_CODEC_CX_FRAME_PKT => {
                     let frame = pkt_ref.data.frame.as_ref();
-                    PacketKind::EncodedFrame(Frame {
-                        data: slice::from_raw_parts(frame.buf as *mut u8, frame.sz as usize),
-                        pts: frame.pts,
-                        duration: frame.duration,
-                        flags: frame.flags,
-                        partition_id: frame.partition_id,
-                    })
+                    let data = slice::from_raw_parts(frame.buf as *mut u8, frame.sz as usize);
+                    if let Some(ref mut stats) = self.stats {
+                        if frame.flags & ffi::VPX_FRAME_IS_FRAGMENT == 0 {
+                            stats.record_output(frame.pts,
+                                                frame.duration as u64,
+                                                frame.sz as usize,
+                                                frame.flags & ffi::VPX_FRAME_IS_KEY != 0);
+                        }
+                    }
+                    let layers = if self.layer_sizes.len() > 1 {
+                        split_superframe(data)
+                    } else {
+                        vec![data]
+                    };
+                    for (i, layer) in layers.into_iter().enumerate() {
+                        let spatial = self.layer_id.spatial as usize + i;
+                        let parsed = match self.frame_sizes {
+                            Some(ref mut parser) => parser.frame_size(layer, frame.partition_id),
+                            None => None,
+                        };
+                        let configured = self.layer_sizes.get(spatial)
+                                                         .or(self.layer_sizes.last())
+                                                         .cloned();
+                        let (width, height) = parsed.or(configured).unwrap_or((0, 0));
+                        let layer_id = LayerId {
+                            spatial: spatial as u32,
+                            temporal: self.layer_id.temporal,
+                        };
+                        if let Some(ref mut stats) = self.stats {
+                            stats.record_layer(layer_id, layer.len());
+                        }
+                        self.pending.push_back(Frame {
+                            data: layer,
+                            pts: frame.pts,
+                            duration: frame.duration,
+                            flags: frame.flags,
+                            partition_id: frame.partition_id,
+                            layer_id: layer_id,
+                            width: width,
+                            height: height,
+                        });
+                    }
+                    match self.pending.pop_front() {
+                        Some(frame) => PacketKind::EncodedFrame(frame),
+                        None => PacketKind::Unknown,
+                    }
                 },
                 ffi::vpx_codec_cx_pkt_kind::VPX_CODEC_STATS_PKT => {
                     // TODO: implement an appopriate structure to hold the data when it's needed
@@ -106,3 +220,33 @@ impl<'encoder> Iterator for FramesIter<'encoder> {
         })
     } }
 }
+
+/// Splits a VP9 superframe into the frames it consists of (see Annex B of the VP9 bitstream
+/// specification). The data is returned as a single frame if it has no valid superframe index.
+/// The index carries no layer ids, so the callers can only number the frames by their position.
+pub fn split_superframe(data: &[u8]) -> Vec<&[u8]> {
+    let marker = match data.last() {
+        Some(&marker) if marker & 0xe0 == 0xc0 => marker,
+        _ => return vec![data],
+    };
+    let frames = (marker & 0x7) as usize + 1;
+    let mag = ((marker >> 3) & 0x3) as usize + 1;
+    let index_size = 2 + mag * frames;
+    if data.len() < index_size || data[data.len() - index_size] != marker {
+        return vec![data];
+    }
+
+    let payload_size = data.len() - index_size;
+    let sizes = &data[payload_size + 1..];
+    let mut result = Vec::with_capacity(frames);
+    let mut offset = 0;
+    for size in sizes.chunks(mag).take(frames) {
+        let size = size.iter().rev().fold(0, |acc, byte| (acc << 8) | *byte as usize);
+        if offset + size > payload_size {
+            return vec![data];
+        }
+        result.push(&data[offset..offset + size]);
+        offset += size;
+    }
+    result
+}
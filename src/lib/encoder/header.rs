@@ -0,0 +1,290 @@
+//! Parsing of the coded frame size from the headers of the encoded VP8/VP9 frames.
+
+use encoder::frame::split_superframe;
+use encoder::svc::REFERENCE_BUFFERS;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Bitstream {
+    VP8,
+    VP9,
+}
+
+/// Tracks the coded frame size of an encoded stream by parsing the headers of its frames. Unlike
+/// the configuration, the headers follow the internal scaling and the spatial layers exactly,
+/// including the frames which the encoder has delayed.
+#[derive(Clone, Debug)]
+pub struct FrameSizeParser {
+    bitstream: Bitstream,
+    // The size of the last VP8 keyframe.
+    keyframe_size: Option<(u32, u32)>,
+    // The sizes of the frames in the VP9 reference buffers.
+    references: [(u32, u32); REFERENCE_BUFFERS as usize],
+}
+
+impl FrameSizeParser {
+    pub fn vp8() -> Self {
+        FrameSizeParser::new(Bitstream::VP8)
+    }
+
+    pub fn vp9() -> Self {
+        FrameSizeParser::new(Bitstream::VP9)
+    }
+
+    fn new(bitstream: Bitstream) -> Self {
+        FrameSizeParser {
+            bitstream: bitstream,
+            keyframe_size: None,
+            references: [(0, 0); REFERENCE_BUFFERS as usize],
+        }
+    }
+
+    /// Returns the coded size of the encoded frame, `partition` is the partition id of the packet
+    /// (only the first partition contains the frame header). The frames have to be passed in the
+    /// order of the stream. Returns `None` if the header cannot be parsed.
+    pub fn frame_size(&mut self, data: &[u8], partition: i32) -> Option<(u32, u32)> {
+        if partition > 0 {
+            return self.keyframe_size;
+        }
+
+        match self.bitstream {
+            Bitstream::VP8 => {
+                // Only the keyframes carry the size, which the other frames keep.
+                if let Some(size) = vp8_keyframe_size(data) {
+                    self.keyframe_size = Some(size);
+                }
+                self.keyframe_size
+            },
+            Bitstream::VP9 => {
+                // A superframe carries the hidden frames before the shown one, all of them may
+                // refresh the reference buffers.
+                let mut size = None;
+                for frame in split_superframe(data) {
+                    size = self.vp9_frame_size(frame);
+                }
+                size
+            },
+        }
+    }
+
+    /// Parses the size from the uncompressed header of a VP9 frame (section 6.2 of the VP9
+    /// bitstream specification) and updates the reference buffers which the frame refreshes.
+    fn vp9_frame_size(&mut self, data: &[u8]) -> Option<(u32, u32)> {
+        let mut header = BitReader::new(data);
+        if header.read(2) != 2 {
+            return None;
+        }
+        let profile_low = header.read(1);
+        let profile = profile_low | header.read(1) << 1;
+        if profile == 3 {
+            header.read(1);
+        }
+        if header.read(1) == 1 {
+            // `show_existing_frame` shows a reference buffer once more.
+            let size = self.references[header.read(3) as usize];
+            return if header.is_exhausted() { None } else { Some(size) };
+        }
+
+        let keyframe = header.read(1) == 0;
+        let show_frame = header.read(1) == 1;
+        let error_resilient = header.read(1) == 1;
+        let (refresh, size) = if keyframe {
+            if !read_sync_code(&mut header) {
+                return None;
+            }
+            read_color_config(&mut header, profile);
+            (0xff, read_size(&mut header))
+        } else {
+            let intra_only = !show_frame && header.read(1) == 1;
+            if !error_resilient {
+                // reset_frame_context
+                header.read(2);
+            }
+            if intra_only {
+                if !read_sync_code(&mut header) {
+                    return None;
+                }
+                if profile > 0 {
+                    read_color_config(&mut header, profile);
+                }
+                let refresh = header.read(8);
+                (refresh, read_size(&mut header))
+            } else {
+                let refresh = header.read(8);
+                let mut ref_frames = [0; 3];
+                for ref_frame in &mut ref_frames {
+                    *ref_frame = header.read(3) as usize;
+                    // ref_frame_sign_bias
+                    header.read(1);
+                }
+                // The size is either taken from one of the references or coded explicitly.
+                let found = ref_frames.iter().cloned().find(|_| header.read(1) == 1);
+                let size = match found {
+                    Some(ref_frame) => self.references[ref_frame],
+                    None => read_size(&mut header),
+                };
+                (refresh, size)
+            }
+        };
+        if header.is_exhausted() {
+            return None;
+        }
+
+        for (slot, reference) in self.references.iter_mut().enumerate() {
+            if refresh & (1 << slot) != 0 {
+                *reference = size;
+            }
+        }
+        Some(size)
+    }
+}
+
+/// Parses the size from the header of a VP8 keyframe (section 9.1 of RFC 6386), returns `None`
+/// for the other frames.
+fn vp8_keyframe_size(data: &[u8]) -> Option<(u32, u32)> {
+    if data.len() < 10 || data[0] & 1 != 0 || data[3..6] != [0x9d, 0x01, 0x2a] {
+        return None;
+    }
+    // The upper two bits tell the decoder to upscale the frame, the coded size is the rest.
+    let width = (data[6] as u32 | (data[7] as u32) << 8) & 0x3fff;
+    let height = (data[8] as u32 | (data[9] as u32) << 8) & 0x3fff;
+    Some((width, height))
+}
+
+fn read_sync_code(header: &mut BitReader) -> bool {
+    header.read(8) == 0x49 && header.read(8) == 0x83 && header.read(8) == 0x42
+}
+
+/// Skips the `color_config()` syntax element of the VP9 header.
+fn read_color_config(header: &mut BitReader, profile: u32) {
+    if profile >= 2 {
+        // ten_or_twelve_bit
+        header.read(1);
+    }
+    let subsampling = profile == 1 || profile == 3;
+    // CS_RGB has no color range and no subsampling.
+    if header.read(3) != 7 {
+        header.read(1);
+        if subsampling {
+            header.read(3);
+        }
+    } else if subsampling {
+        header.read(1);
+    }
+}
+
+fn read_size(header: &mut BitReader) -> (u32, u32) {
+    let width = header.read(16) + 1;
+    let height = header.read(16) + 1;
+    (width, height)
+}
+
+/// Reads the bits of the data, the most significant bit first. The bits past the end of the data
+/// are read as zeros, see `is_exhausted()`.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader {
+            data: data,
+            position: 0,
+        }
+    }
+
+    fn read(&mut self, bits: u32) -> u32 {
+        let mut value = 0;
+        for _ in 0..bits {
+            let byte = self.data.get(self.position / 8).cloned().unwrap_or(0);
+            value = (value << 1) | ((byte >> (7 - self.position % 8)) & 1) as u32;
+            self.position += 1;
+        }
+        value
+    }
+
+    /// Whether more bits have been read than the data contains.
+    fn is_exhausted(&self) -> bool {
+        self.position > self.data.len() * 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameSizeParser;
+
+    // Packs the `(value, bits)` fields, the most significant bit first.
+    fn pack(fields: &[(u32, u32)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut position = 0;
+        for &(value, bits) in fields {
+            for bit in (0..bits).rev() {
+                if position % 8 == 0 {
+                    data.push(0);
+                }
+                *data.last_mut().unwrap() |= (((value >> bit) & 1) as u8) << (7 - position % 8);
+                position += 1;
+            }
+        }
+        data
+    }
+
+    fn vp9_keyframe(width: u32, height: u32) -> Vec<u8> {
+        // Profile 0, shown, BT.601 limited range.
+        pack(&[(2, 2), (0, 2), (0, 1), (0, 1), (1, 1), (0, 1),
+               (0x49, 8), (0x83, 8), (0x42, 8), (1, 3), (0, 1),
+               (width - 1, 16), (height - 1, 16), (0, 8)])
+    }
+
+    fn vp9_inter_frame(refresh: u32, found_ref: &[u32], size: Option<(u32, u32)>) -> Vec<u8> {
+        let mut fields = vec![(2, 2), (0, 2), (0, 1), (1, 1), (1, 1), (0, 1), (0, 2),
+                              (refresh, 8), (0, 4), (2, 4), (4, 4)];
+        fields.extend(found_ref.iter().map(|&found| (found, 1)));
+        if let Some((width, height)) = size {
+            fields.push((width - 1, 16));
+            fields.push((height - 1, 16));
+        }
+        fields.push((0, 8));
+        pack(&fields)
+    }
+
+    #[test]
+    fn vp8_sizes_come_from_the_keyframes() {
+        let mut parser = FrameSizeParser::vp8();
+        assert_eq!(parser.frame_size(&[0x11, 0, 0, 0, 0, 0, 0, 0, 0, 0], -1), None);
+
+        // 320x180 with the horizontal upscaling bits set.
+        let keyframe = [0x10, 0x02, 0x00, 0x9d, 0x01, 0x2a, 0x40, 0x41, 0xb4, 0x00];
+        assert_eq!(parser.frame_size(&keyframe, -1), Some((320, 180)));
+        assert_eq!(parser.frame_size(&[0x11, 0, 0, 0, 0, 0, 0, 0, 0, 0], -1), Some((320, 180)));
+        assert_eq!(parser.frame_size(&[0xff], 1), Some((320, 180)));
+    }
+
+    #[test]
+    fn vp9_sizes_follow_the_references() {
+        let mut parser = FrameSizeParser::vp9();
+        assert_eq!(parser.frame_size(&vp9_keyframe(640, 360), -1), Some((640, 360)));
+
+        // An explicitly sized frame which refreshes the buffer 1 only.
+        let scaled = vp9_inter_frame(0b10, &[0, 0, 0], Some((320, 180)));
+        assert_eq!(parser.frame_size(&scaled, -1), Some((320, 180)));
+
+        // The references are the buffers 0, 1 and 2, the size is taken from the second one.
+        let inter = vp9_inter_frame(0, &[0, 1], None);
+        assert_eq!(parser.frame_size(&inter, -1), Some((320, 180)));
+        let inter = vp9_inter_frame(0, &[1], None);
+        assert_eq!(parser.frame_size(&inter, -1), Some((640, 360)));
+
+        // show_existing_frame of the buffer 1.
+        assert_eq!(parser.frame_size(&pack(&[(2, 2), (0, 2), (1, 1), (1, 3)]), -1),
+                   Some((320, 180)));
+    }
+
+    #[test]
+    fn vp9_truncated_headers_are_rejected() {
+        let mut parser = FrameSizeParser::vp9();
+        let keyframe = vp9_keyframe(640, 360);
+        assert_eq!(parser.frame_size(&keyframe[..6], -1), None);
+        assert_eq!(parser.frame_size(&[0x00], -1), None);
+    }
+}
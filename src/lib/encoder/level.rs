@@ -0,0 +1,178 @@
+//! VP9 levels, which constrain the stream so that it can be decoded by the hardware decoders.
+
+use ffi;
+
+use encoder::{Encoder, EncoderConfig};
+use encoder::vp9::VP9;
+use error::{CodecError, Error, VPXResult as Result};
+
+/// A VP9 level as defined by the VP9 specification (`VP9E_SET_TARGET_LEVEL`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Vp9Level {
+    /// The encoder keeps the statistics of the stream so that the level can be queried with
+    /// `Encoder::level()`, but the stream is not constrained to any level.
+    Auto,
+    Level1,
+    Level1_1,
+    Level2,
+    Level2_1,
+    Level3,
+    Level3_1,
+    Level4,
+    Level4_1,
+    Level5,
+    Level5_1,
+    Level5_2,
+    Level6,
+    Level6_1,
+    Level6_2,
+}
+
+/// The limits of a level, see `Vp9Level::limits()`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LevelLimits {
+    /// The maximum number of the luma samples per second.
+    pub max_luma_sample_rate: u64,
+    /// The maximum number of the luma samples in a frame.
+    pub max_luma_picture_size: u32,
+    /// The maximum width or height of a frame.
+    pub max_luma_picture_breadth: u32,
+    /// The maximum average bitrate in kbps.
+    pub max_bitrate: u32,
+}
+
+const LEVELS: [Vp9Level; 14] = [
+    Vp9Level::Level1,
+    Vp9Level::Level1_1,
+    Vp9Level::Level2,
+    Vp9Level::Level2_1,
+    Vp9Level::Level3,
+    Vp9Level::Level3_1,
+    Vp9Level::Level4,
+    Vp9Level::Level4_1,
+    Vp9Level::Level5,
+    Vp9Level::Level5_1,
+    Vp9Level::Level5_2,
+    Vp9Level::Level6,
+    Vp9Level::Level6_1,
+    Vp9Level::Level6_2,
+];
+
+impl Vp9Level {
+    /// Returns the level with the given value as used by libvpx (e.g. `41` for the level 4.1).
+    pub fn from_value(value: i32) -> Option<Vp9Level> {
+        if value == Vp9Level::Auto.value() {
+            return Some(Vp9Level::Auto);
+        }
+        LEVELS.iter().find(|level| level.value() == value).cloned()
+    }
+
+    /// Returns the value of the level as used by libvpx: `1` for `Auto` and `10 * major + minor`
+    /// otherwise.
+    pub fn value(&self) -> i32 {
+        match *self {
+            Vp9Level::Auto => 1,
+            Vp9Level::Level1 => 10,
+            Vp9Level::Level1_1 => 11,
+            Vp9Level::Level2 => 20,
+            Vp9Level::Level2_1 => 21,
+            Vp9Level::Level3 => 30,
+            Vp9Level::Level3_1 => 31,
+            Vp9Level::Level4 => 40,
+            Vp9Level::Level4_1 => 41,
+            Vp9Level::Level5 => 50,
+            Vp9Level::Level5_1 => 51,
+            Vp9Level::Level5_2 => 52,
+            Vp9Level::Level6 => 60,
+            Vp9Level::Level6_1 => 61,
+            Vp9Level::Level6_2 => 62,
+        }
+    }
+
+    /// Returns the limits of the level (as in `vp9_level_defs` of libvpx), `None` for `Auto`.
+    pub fn limits(&self) -> Option<LevelLimits> {
+        let (sample_rate, picture_size, breadth, bitrate) = match *self {
+            Vp9Level::Auto => return None,
+            Vp9Level::Level1 => (829_440, 36_864, 512, 200),
+            Vp9Level::Level1_1 => (2_764_800, 73_728, 768, 800),
+            Vp9Level::Level2 => (4_608_000, 122_880, 960, 1_800),
+            Vp9Level::Level2_1 => (9_216_000, 245_760, 1_344, 3_600),
+            Vp9Level::Level3 => (20_736_000, 552_960, 2_048, 7_200),
+            Vp9Level::Level3_1 => (36_864_000, 983_040, 2_752, 12_000),
+            Vp9Level::Level4 => (83_558_400, 2_228_224, 4_160, 18_000),
+            Vp9Level::Level4_1 => (160_432_128, 2_228_224, 4_160, 30_000),
+            Vp9Level::Level5 => (311_951_360, 8_912_896, 8_384, 60_000),
+            Vp9Level::Level5_1 => (588_251_136, 8_912_896, 8_384, 120_000),
+            Vp9Level::Level5_2 => (1_176_502_272, 8_912_896, 8_384, 180_000),
+            Vp9Level::Level6 => (1_176_502_272, 35_651_584, 16_832, 180_000),
+            Vp9Level::Level6_1 => (2_353_004_544, 35_651_584, 16_832, 240_000),
+            Vp9Level::Level6_2 => (4_706_009_088, 35_651_584, 16_832, 480_000),
+        };
+        Some(LevelLimits {
+            max_luma_sample_rate: sample_rate,
+            max_luma_picture_size: picture_size,
+            max_luma_picture_breadth: breadth,
+            max_bitrate: bitrate,
+        })
+    }
+
+    /// Checks that the frame dimensions, the frame rate and the target bitrate of the
+    /// configuration are within the limits of the level. The frame rate is only checked if it
+    /// has been set with `EncoderConfig::set_frame_rate()`, since the timebase does not tell it.
+    pub fn validate<Enc>(&self, config: &EncoderConfig<Enc>) -> Result<()> {
+        let limits = match self.limits() {
+            Some(limits) => limits,
+            None => return Ok(()),
+        };
+        let (width, height) = config.frame_dimensions();
+        let picture_size = width as u64 * height as u64;
+        // Without the frame rate the sample rate is not checked.
+        let sample_rate = config.frame_rate().map_or(0.0, |rate| picture_size as f64 * rate);
+
+        let violation = if picture_size > limits.max_luma_picture_size as u64 {
+            Some(format!("the frame size {}x{} exceeds {} samples",
+                         width, height, limits.max_luma_picture_size))
+        } else if width.max(height) > limits.max_luma_picture_breadth {
+            Some(format!("the frame size {}x{} exceeds {} pixels in width or height",
+                         width, height, limits.max_luma_picture_breadth))
+        } else if sample_rate > limits.max_luma_sample_rate as f64 {
+            Some(format!("{:.0} samples per second exceed {}",
+                         sample_rate, limits.max_luma_sample_rate))
+        } else if config.target_bitrate() > limits.max_bitrate {
+            Some(format!("the bitrate of {} kbps exceeds {} kbps",
+                         config.target_bitrate(), limits.max_bitrate))
+        } else {
+            None
+        };
+
+        match violation {
+            Some(detail) => Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "VP9E_SET_TARGET_LEVEL",
+                format!("{} for the level {:?}", detail, self))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Encoder<VP9> {
+    /// Constrains the stream to the given level (`VP9E_SET_TARGET_LEVEL`). Prefer
+    /// `EncoderConfig::set_target_level()`, which validates the configuration against the level
+    /// before the encoder is created.
+    pub fn set_target_level(&mut self, level: Vp9Level) -> Result<()> {
+        self.control(ffi::vp8e_enc_control_id::VP9E_SET_TARGET_LEVEL,
+                     level.value(),
+                     "VP9E_SET_TARGET_LEVEL")
+    }
+
+    /// Returns the level of the stream encoded so far (`VP9E_GET_LEVEL`). The encoder only keeps
+    /// track of the level if a target level (possibly `Vp9Level::Auto`) has been set, `None` is
+    /// returned if the level is unknown.
+    pub fn level(&mut self) -> Result<Option<Vp9Level>> {
+        let mut level: i32 = 0;
+        self.control_ptr(ffi::vp8e_enc_control_id::VP9E_GET_LEVEL, &mut level, "VP9E_GET_LEVEL")?;
+        Ok(Vp9Level::from_value(level).and_then(|level| {
+            if level == Vp9Level::Auto { None } else { Some(level) }
+        }))
+    }
+}
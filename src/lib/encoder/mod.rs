@@ -3,20 +3,46 @@
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::ptr::null;
+use std::time::Instant;
 
 use ffi;
 use libc;
 use time::Duration;
 
+use capabilities::Capabilities;
 use context::CodecContext as Context;
-use error::{VPXResult as Result, check_err};
-use image::Image;
+use error::{CodecError, Error, VPXResult as Result, check_ctx_err};
+use image::{ColorRange, ColorSpace, Format, Image};
 
-pub use self::frame::{Frame, FramesIter};
-pub use self::config::{CodecFlags, EncoderConfig};
+use self::header::FrameSizeParser;
+use self::stats::query_quantizer;
 
+pub use self::frame::{Frame, FramesIter, LayerId, PacketKind};
+pub use self::active::{ACTIVE_MAP_BLOCK_SIZE, ActiveMap};
+pub use self::alpha::{AlphaEncoder, AlphaFrame};
+pub use self::config::{BitDepth, CodecFlags, EncoderConfig, RateControl};
+pub use self::controls::{ContentType, Scaling, ScalingMode, Tuning};
+pub use self::level::{LevelLimits, Vp9Level};
+pub use self::reference::ReferenceFlags;
+pub use self::roi::{ROI_SEGMENTS, RoiMap};
+pub use self::simulcast::{SimulcastEncoder, SimulcastFramesIter};
+pub use self::stats::EncoderStats;
+pub use self::svc::{REFERENCE_BUFFERS, SvcParameters, SvcRefFrameConfig};
+pub use self::temporal::TemporalLayering;
+
+mod active;
+mod alpha;
 mod config;
+mod controls;
 mod frame;
+mod header;
+mod level;
+mod reference;
+mod roi;
+mod simulcast;
+mod stats;
+mod svc;
+mod temporal;
 pub mod vp8;
 pub mod vp9;
 
@@ -28,41 +54,132 @@ pub trait VpxEncoder {
     // type CodecFlags: Deref<Target=ffi::vpx_codec_flags_t>;
 
     /// Codec specific frame flags. The set of encoding flags differs between VP8 and VP9.
-    type FrameFlags: Into<ffi::vpx_enc_frame_flags_t> + Default;
+    type FrameFlags: Into<ffi::vpx_enc_frame_flags_t> + Default + From<ReferenceFlags>;
 
     /// Returns a reference to the codec interface (some sort of opaque data structure inside
     /// libvpx.
     fn interface() -> *mut ffi::vpx_codec_iface_t;
+
+    /// Returns the range of the values accepted by `Encoder::set_cpu_used()`.
+    fn cpu_used_range() -> (i32, i32);
+
+    /// Returns the size of the blocks (in pixels) the `RoiMap` assigns to the segments.
+    fn roi_block_size() -> u32;
+
+    /// Returns the `cpu_used` value used by `EncoderConfig::screen_share()`.
+    fn screen_share_cpu_used() -> i32;
+
+    /// Returns the parser of the coded frame size from the headers of the encoded frames. Without
+    /// a parser the frames report the configured size.
+    fn frame_size_parser() -> Option<FrameSizeParser> {
+        None
+    }
+
+    /// Tunes the encoder for the given type of content.
+    fn set_content_type(encoder: &mut Encoder<Self>, content_type: ContentType) -> Result<()>
+        where Self: Sized;
+
+    /// Constrains the stream to the given level, which only `EncoderConfig<VP9>` can set.
+    fn set_target_level(_encoder: &mut Encoder<Self>, _level: Vp9Level) -> Result<()>
+        where Self: Sized
+    {
+        Ok(())
+    }
+
+    /// Signals the color space and range of the input in the stream.
+    fn signal_color(encoder: &mut Encoder<Self>, space: ColorSpace, range: ColorRange)
+        -> Result<()> where Self: Sized;
+
+    /// Performs the codec specific initialization which is needed when the encoder has been
+    /// configured with more than one temporal or spatial layer.
+    fn enable_layers(encoder: &mut Encoder<Self>) -> Result<()> where Self: Sized;
+
+    /// Sets the temporal layer of the frames which are encoded next.
+    fn set_temporal_layer_id(encoder: &mut Encoder<Self>, layer_id: u32) -> Result<()>
+        where Self: Sized;
+}
+
+/// Returns the capabilities of the encoder for the given codec.
+pub fn capabilities<Enc: VpxEncoder>() -> Capabilities {
+    Capabilities::from_encoder_caps(unsafe { ffi::vpx_codec_get_caps(Enc::interface()) })
 }
 
 /// An instance of libvpx-based encoder, you have to specify which codec you want to use, current
 /// the supported encoders are: `Encoder<VP8>`, `Encoder<VP9>`.
 pub struct Encoder<Enc: VpxEncoder> {
     context: Context,
+    config: ffi::vpx_codec_enc_cfg_t,
+    layer_id: LayerId,
+    scaling_mode: ScalingMode,
+    // Scaling factors of the VP9 spatial layers.
+    layer_scaling: Vec<(u32, u32)>,
+    frame_sizes: Option<FrameSizeParser>,
+    stats: EncoderStats,
+    // Color space and range which have been signaled in the stream.
+    color: Option<(ColorSpace, ColorRange)>,
+    // Whether the color space and range have been set explicitly, in which case they are not
+    // signaled from the images anymore.
+    explicit_color: (bool, bool),
     _phantom: PhantomData<Enc>,
 }
 
 impl<Enc: VpxEncoder> Encoder<Enc> {
     /// Creates a new encoder given the configurations given by the user. In case if the
-    /// configurations are not explicitly specified, the default configuration will be used.
+    /// configurations are not explicitly specified, the default configuration will be used. The
+    /// flags are validated against the encoder capabilities before the encoder is initialized.
     pub fn new(config: Option<EncoderConfig<Enc>>, flags: Option<CodecFlags>) -> Result<Self> {
         let iface = Enc::interface();
         let config = config.unwrap_or(EncoderConfig::<Enc>::new()?);
         let flags = flags.unwrap_or(CodecFlags::default());
+        flags.validate_for_encoder(&capabilities::<Enc>())?;
+        config.validate_bit_depth(&flags)?;
+        let cpu_used = config.cpu_used();
+        let content_type = config.content_type();
+        let target_level = config.target_level();
+        if let Some(level) = target_level {
+            level.validate(&config)?;
+        }
+        let config: ffi::vpx_codec_enc_cfg_t = config.into();
         let mut ctx = Context::new();
-        check_err(unsafe { ffi::vpx_codec_enc_init_ver(&mut *ctx,
+        let res = unsafe { ffi::vpx_codec_enc_init_ver(&mut *ctx,
                                                        iface,
-                                                       &config.into(),
+                                                       &config,
                                                        flags.into(),
-                                                       ffi::VPX_ENCODER_ABI_VERSION as i32) })?;
-        Ok(Encoder {
+                                                       ffi::VPX_ENCODER_ABI_VERSION as i32) };
+        check_ctx_err(&mut ctx, res, "vpx_codec_enc_init")?;
+        let has_layers = config.ts_number_layers > 1 || config.ss_number_layers > 1;
+        let stats = EncoderStats::new(config.g_timebase.num, config.g_timebase.den);
+        let mut encoder = Encoder {
             context: ctx,
+            config: config,
+            layer_id: LayerId::default(),
+            scaling_mode: ScalingMode::default(),
+            layer_scaling: Vec::new(),
+            frame_sizes: Enc::frame_size_parser(),
+            stats: stats,
+            color: None,
+            explicit_color: (false, false),
             _phantom: PhantomData { },
-        })
+        };
+        if has_layers {
+            Enc::enable_layers(&mut encoder)?;
+        }
+        if let Some(cpu_used) = cpu_used {
+            encoder.set_cpu_used(cpu_used)?;
+        }
+        if let Some(content_type) = content_type {
+            encoder.set_content_type(content_type)?;
+        }
+        if let Some(level) = target_level {
+            Enc::set_target_level(&mut encoder, level)?;
+        }
+        Ok(encoder)
     }
 
     /// Encodes a single frame, fails in case if the encoding cannot be done. Refer to
-    /// `vpx_codec_encode()` to get more info about each parameter.
+    /// `vpx_codec_encode()` to get more info about each parameter. The encoding time is recorded
+    /// in the statistics (see `stats()`). The color space and range of the image are signaled in
+    /// the stream unless they have been set explicitly (VP9 only).
     pub fn encode(&mut self,
                   image: &Image,
                   pts: ffi::vpx_codec_pts_t,
@@ -71,12 +188,49 @@ impl<Enc: VpxEncoder> Encoder<Enc> {
                   deadline: Deadline)
         -> Result<()>
     {
-        check_err(unsafe { ffi::vpx_codec_encode(&mut *self.context,
+        self.encode_raw(image, pts, duration, flags.into(), deadline)
+    }
+
+    /// Same as `encode()`, but takes the frame flags of libvpx.
+    fn encode_raw(&mut self,
+                  image: &Image,
+                  pts: ffi::vpx_codec_pts_t,
+                  duration: u64,
+                  flags: ffi::vpx_enc_frame_flags_t,
+                  deadline: Deadline)
+        -> Result<()>
+    {
+        self.check_image(image)?;
+        let color = (image.color_space(), image.color_range());
+        if self.color != Some(color) {
+            Enc::signal_color(self, color.0, color.1)?;
+            self.color = Some(color);
+        }
+        let started = Instant::now();
+        let res = unsafe { ffi::vpx_codec_encode(&mut *self.context,
                                                  image.deref(),
                                                  pts,
                                                  duration,
-                                                 flags.into(),
-                                                 deadline.into()) })?;
+                                                 flags,
+                                                 deadline.clone().into()) };
+        check_ctx_err(&mut self.context, res, "vpx_codec_encode")?;
+        self.stats.record_input(&deadline, started.elapsed());
+        // Without the lag the encoder codes the image right away, so the last quantizer is the one
+        // of the image (unless the rate control has dropped it).
+        if self.config.g_lag_in_frames == 0 {
+            if let Some(quantizer) = query_quantizer(&mut *self.context) {
+                self.stats.record_encoded_quantizer(pts, quantizer);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the temporal layer of the frames which are encoded next (see `TemporalLayering`). The
+    /// layer id is attached to the frames returned by `frames_iter()`, which is only accurate if
+    /// the encoder does not delay the frames (i.e. `g_lag_in_frames` is 0).
+    pub fn set_temporal_layer_id(&mut self, layer_id: u32) -> Result<()> {
+        Enc::set_temporal_layer_id(self, layer_id)?;
+        self.layer_id.temporal = layer_id;
         Ok(())
     }
 
@@ -84,7 +238,85 @@ impl<Enc: VpxEncoder> Encoder<Enc> {
     /// call this function directly after `encode()`, but you are not obliged to. You cannot call
     /// any encoding functions while you own a frame iterator.
     pub fn frames_iter(&mut self) -> FramesIter {
-        FramesIter::new(&mut self.context)
+        let layer_sizes = self.layer_sizes();
+        let frames = FramesIter::new(&mut self.context, self.layer_id, layer_sizes)
+                                .with_stats(&mut self.stats);
+        match self.frame_sizes {
+            Some(ref mut parser) => frames.with_frame_sizes(parser),
+            None => frames,
+        }
+    }
+
+    /// Checks that the bit depth, the format and the color space of the image match the
+    /// configuration.
+    fn check_image(&self, image: &Image) -> Result<()> {
+        let format = *image.get_format();
+        let bit_depth = self.config.g_bit_depth as u32;
+        let violation = if format.is_high_bit_depth() != (bit_depth > 8) {
+            Some(format!("{:?} images cannot be encoded with {} bits", format, bit_depth))
+        } else if bit_depth > 8 && image.bit_depth() != self.config.g_input_bit_depth {
+            Some(format!("the image has {} bits, but the input bit depth is {}",
+                         image.bit_depth(), self.config.g_input_bit_depth))
+        } else if bit_depth > 8 && (self.config.g_profile == 2) !=
+                                   (format == Format::I420 { hi_bit_depth: true }) {
+            Some(format!("{:?} images cannot be encoded with the profile {}",
+                         format, self.config.g_profile))
+        } else if image.color_space() == ColorSpace::SRGB && !self.explicit_color.0 &&
+                  self.config.g_profile % 2 == 0 {
+            // VP9 would fail to signal the color space of the image, VP8 has no 4:4:4 at all.
+            Some(format!("sRGB images cannot be encoded with the profile {}, only with the \
+                          profiles 1 and 3", self.config.g_profile))
+        } else {
+            None
+        };
+
+        match violation {
+            Some(detail) => Err(Error::with_detail(CodecError::InvalidParam,
+                                                   "vpx_codec_encode",
+                                                   detail)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the configured frame size of each spatial layer, which the frames report if the
+    /// coded size cannot be parsed from their headers.
+    fn layer_sizes(&self) -> Vec<(u32, u32)> {
+        let (width, height) = (self.config.g_w, self.config.g_h);
+        (0..self.config.ss_number_layers.max(1) as usize).map(|layer| {
+            match self.layer_scaling.get(layer) {
+                // Same as `vp9_get_layer_resolution()`, which makes the dimensions even.
+                Some(&(num, den)) if den != 0 => {
+                    let scale = |size: u32| {
+                        let scaled = (size as u64 * num as u64 / den as u64) as u32;
+                        scaled + scaled % 2
+                    };
+                    (scale(width), scale(height))
+                },
+                _ => (width, height),
+            }
+        }).collect()
+    }
+
+    /// Sets an integer codec control (`vpx_codec_control()`).
+    fn control(&mut self,
+               id: ffi::vp8e_enc_control_id,
+               value: libc::c_int,
+               operation: &'static str) -> Result<()>
+    {
+        let res = unsafe { ffi::vpx_codec_control_(&mut *self.context, id as libc::c_int, value) };
+        check_ctx_err(&mut self.context, res, operation)?;
+        Ok(())
+    }
+
+    /// Sets a codec control which takes a pointer to a structure (`vpx_codec_control()`).
+    fn control_ptr<T>(&mut self,
+                      id: ffi::vp8e_enc_control_id,
+                      value: *mut T,
+                      operation: &'static str) -> Result<()>
+    {
+        let res = unsafe { ffi::vpx_codec_control_(&mut *self.context, id as libc::c_int, value) };
+        check_ctx_err(&mut self.context, res, operation)?;
+        Ok(())
     }
 }
 
@@ -97,7 +329,8 @@ impl<Enc: VpxEncoder> Drop for Encoder<Enc> {
         let res = unsafe {
             ffi::vpx_codec_encode(&mut *self.context, null(), -1, 1, 0, Deadline::GoodQuality.into())
         };
-        check_err(res).expect("Could not release the encoder resource");
+        check_ctx_err(&mut self.context, res, "vpx_codec_encode")
+            .expect("Could not release the encoder resource");
         let frames_iter = self.frames_iter();
         for _ in frames_iter {
         }
@@ -105,6 +338,7 @@ impl<Enc: VpxEncoder> Drop for Encoder<Enc> {
 }
 
 /// Soft realtime deadline parameters for libvpx encoder.
+#[derive(Clone, Debug)]
 pub enum Deadline {
     Realtime,
     GoodQuality,
@@ -0,0 +1,82 @@
+//! Per-frame control of the reference buffers which is common for VP8 and VP9 encoders.
+
+use ffi;
+
+/// Controls which reference buffers (last, golden and alternate reference frames) a frame may use
+/// for prediction and which of them are updated with the encoded frame. Both VP8 and VP9 encoders
+/// understand these flags.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReferenceFlags {
+    no_ref_last: bool,
+    no_ref_gf: bool,
+    no_ref_arf: bool,
+    no_upd_last: bool,
+    no_upd_gf: bool,
+    no_upd_arf: bool,
+    no_upd_entropy: bool,
+}
+
+impl ReferenceFlags {
+    /// Do not reference the last frame.
+    pub fn no_ref_last(&mut self, value: bool) {
+        self.no_ref_last = value;
+    }
+
+    /// Do not reference the golden frame.
+    pub fn no_ref_golden(&mut self, value: bool) {
+        self.no_ref_gf = value;
+    }
+
+    /// Do not reference the alternate reference frame.
+    pub fn no_ref_altref(&mut self, value: bool) {
+        self.no_ref_arf = value;
+    }
+
+    /// Do not update the last frame with the encoded frame.
+    pub fn no_update_last(&mut self, value: bool) {
+        self.no_upd_last = value;
+    }
+
+    /// Do not update the golden frame with the encoded frame.
+    pub fn no_update_golden(&mut self, value: bool) {
+        self.no_upd_gf = value;
+    }
+
+    /// Do not update the alternate reference frame with the encoded frame.
+    pub fn no_update_altref(&mut self, value: bool) {
+        self.no_upd_arf = value;
+    }
+
+    /// Do not update the entropy context.
+    pub fn no_update_entropy(&mut self, value: bool) {
+        self.no_upd_entropy = value;
+    }
+}
+
+impl Into<ffi::vpx_enc_frame_flags_t> for ReferenceFlags {
+    fn into(self) -> ffi::vpx_enc_frame_flags_t {
+        let mut flags: ffi::vpx_enc_frame_flags_t = 0;
+        if self.no_ref_last {
+            flags |= ffi::VP8_EFLAG_NO_REF_LAST as i64;
+        }
+        if self.no_ref_gf {
+            flags |= ffi::VP8_EFLAG_NO_REF_GF as i64;
+        }
+        if self.no_ref_arf {
+            flags |= ffi::VP8_EFLAG_NO_REF_ARF as i64;
+        }
+        if self.no_upd_last {
+            flags |= ffi::VP8_EFLAG_NO_UPD_LAST as i64;
+        }
+        if self.no_upd_gf {
+            flags |= ffi::VP8_EFLAG_NO_UPD_GF as i64;
+        }
+        if self.no_upd_arf {
+            flags |= ffi::VP8_EFLAG_NO_UPD_ARF as i64;
+        }
+        if self.no_upd_entropy {
+            flags |= ffi::VP8_EFLAG_NO_UPD_ENTROPY as i64;
+        }
+        flags
+    }
+}
@@ -0,0 +1,181 @@
+//! Region of interest (ROI) maps, which let the encoder spend different amount of bits on
+//! different regions of the frame.
+
+use std::cmp;
+
+use ffi;
+
+use encoder::{Encoder, VpxEncoder};
+use error::{CodecError, Error, VPXResult as Result};
+
+/// The number of segments an `RoiMap` may use.
+pub const ROI_SEGMENTS: usize = 4;
+
+/// Assigns each block of the frame to one of `ROI_SEGMENTS` segments and specifies the encoding
+/// parameters of each segment, wraps `vpx_roi_map_t`. The blocks are 16x16 pixels for VP8 and
+/// 8x8 pixels for VP9, use `Encoder::new_roi_map()` to get a map of the right size.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoiMap {
+    rows: u32,
+    cols: u32,
+    block_size: u32,
+    segments: Vec<u8>,
+    delta_q: [i32; ROI_SEGMENTS],
+    delta_lf: [i32; ROI_SEGMENTS],
+    static_threshold: [u32; ROI_SEGMENTS],
+}
+
+impl RoiMap {
+    /// Creates a map for a frame of the given dimensions in pixels with all blocks assigned to
+    /// segment 0. Panics if `block_size` is 0.
+    pub fn new(width: u32, height: u32, block_size: u32) -> Self {
+        assert!(block_size > 0, "the block size of an ROI map must not be 0");
+        let rows = blocks(height as u64, block_size) as u32;
+        let cols = blocks(width as u64, block_size) as u32;
+        RoiMap {
+            rows: rows,
+            cols: cols,
+            block_size: block_size,
+            segments: vec![0; rows as usize * cols as usize],
+            delta_q: [0; ROI_SEGMENTS],
+            delta_lf: [0; ROI_SEGMENTS],
+            static_threshold: [0; ROI_SEGMENTS],
+        }
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    pub fn cols(&self) -> u32 {
+        self.cols
+    }
+
+    /// The size of the (square) block in pixels.
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    /// The segment of each block, row by row.
+    pub fn segments(&self) -> &[u8] {
+        &self.segments
+    }
+
+    /// Assigns a single block to the segment.
+    pub fn set_block(&mut self, row: u32, col: u32, segment: u8) -> Result<()> {
+        check_segment(segment as usize)?;
+        if row >= self.rows || col >= self.cols {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "VP8E_SET_ROI_MAP",
+                format!("block ({}, {}) is outside of the {}x{} map",
+                        row, col, self.rows, self.cols)));
+        }
+        self.segments[row as usize * self.cols as usize + col as usize] = segment;
+        Ok(())
+    }
+
+    /// Assigns all blocks which overlap with the given rectangle (in pixels) to the segment. The
+    /// part of the rectangle which is outside of the frame is ignored.
+    pub fn set_region(&mut self, x: u32, y: u32, width: u32, height: u32, segment: u8)
+        -> Result<()>
+    {
+        check_segment(segment as usize)?;
+        let first_row = cmp::min(y / self.block_size, self.rows);
+        let first_col = cmp::min(x / self.block_size, self.cols);
+        let last_row = cmp::min(blocks(y as u64 + height as u64, self.block_size),
+                                self.rows as u64) as u32;
+        let last_col = cmp::min(blocks(x as u64 + width as u64, self.block_size),
+                                self.cols as u64) as u32;
+        for row in first_row..last_row {
+            let start = row as usize * self.cols as usize;
+            for block in &mut self.segments[start + first_col as usize..start + last_col as usize] {
+                *block = segment;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the encoding parameters of the segment: the quantizer delta and the loop filter
+    /// level delta (both in the range -63..63) and the threshold of the static blocks, i.e.
+    /// the blocks which are encoded without residual (`0` disables it).
+    pub fn set_segment_params(&mut self,
+                              segment: u8,
+                              delta_q: i32,
+                              delta_lf: i32,
+                              static_threshold: u32) -> Result<()>
+    {
+        check_segment(segment as usize)?;
+        for &(name, value) in &[("delta_q", delta_q), ("delta_lf", delta_lf)] {
+            if value < -63 || value > 63 {
+                return Err(Error::with_detail(
+                    CodecError::InvalidParam,
+                    "VP8E_SET_ROI_MAP",
+                    format!("{} {} of segment {} is out of range -63..63",
+                            name, value, segment)));
+            }
+        }
+        let segment = segment as usize;
+        self.delta_q[segment] = delta_q;
+        self.delta_lf[segment] = delta_lf;
+        self.static_threshold[segment] = static_threshold;
+        Ok(())
+    }
+}
+
+/// Returns the number of blocks of the given size needed to cover the pixels.
+pub fn blocks(pixels: u64, block_size: u32) -> u64 {
+    (pixels + block_size as u64 - 1) / block_size as u64
+}
+
+fn check_segment(segment: usize) -> Result<()> {
+    if segment >= ROI_SEGMENTS {
+        Err(Error::with_detail(CodecError::InvalidParam,
+                               "VP8E_SET_ROI_MAP",
+                               format!("segment {} is out of range 0..{}",
+                                       segment, ROI_SEGMENTS - 1)))
+    } else {
+        Ok(())
+    }
+}
+
+impl<Enc: VpxEncoder> Encoder<Enc> {
+    /// Creates an empty ROI map sized for the configured frame dimensions of the encoder.
+    pub fn new_roi_map(&self) -> RoiMap {
+        RoiMap::new(self.config.g_w, self.config.g_h, Enc::roi_block_size())
+    }
+
+    /// Sets the ROI map which is used for the frames encoded next (`VP8E_SET_ROI_MAP`). The map
+    /// has to match the configured frame dimensions. VP9 supports ROI maps since libvpx 1.8,
+    /// whose map also has per-segment skip and reference frame settings; the segments are never
+    /// skipped and are not restricted to a reference frame.
+    pub fn set_roi_map(&mut self, map: &RoiMap) -> Result<()> {
+        let expected = self.new_roi_map();
+        let dimensions = (map.rows, map.cols, map.block_size);
+        if dimensions != (expected.rows, expected.cols, expected.block_size) {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "VP8E_SET_ROI_MAP",
+                format!("{}x{} map of {}px blocks does not match the {}x{} map of {}px blocks",
+                        map.rows, map.cols, map.block_size,
+                        expected.rows, expected.cols, expected.block_size)));
+        }
+
+        let mut roi: ffi::vpx_roi_map_t = Default::default();
+        roi.enabled = 1;
+        roi.roi_map = map.segments.as_ptr() as *mut u8;
+        roi.rows = map.rows;
+        roi.cols = map.cols;
+        for segment in 0..ROI_SEGMENTS {
+            roi.delta_q[segment] = map.delta_q[segment];
+            roi.delta_lf[segment] = map.delta_lf[segment];
+            roi.static_threshold[segment] = map.static_threshold[segment];
+        }
+        for (skip, ref_frame) in roi.skip.iter_mut().zip(roi.ref_frame.iter_mut()) {
+            *skip = 0;
+            // -1 lets the encoder choose the reference frame of the segment.
+            *ref_frame = -1;
+        }
+        self.control_ptr(ffi::vp8e_enc_control_id::VP8E_SET_ROI_MAP, &mut roi, "VP8E_SET_ROI_MAP")
+    }
+}
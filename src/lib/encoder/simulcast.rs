@@ -0,0 +1,226 @@
+//! Multi-resolution (simulcast) encoding, which encodes the same input in several resolutions
+//! with a single encoder instance.
+
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::ptr::null;
+
+use ffi;
+use libc;
+
+use encoder::{CodecFlags, Deadline, EncoderConfig, FramesIter, LayerId, PacketKind, VpxEncoder};
+use encoder::capabilities;
+use encoder::controls::check_range;
+use encoder::vp8::{FrameFlags, VP8, screen_content_mode};
+use error::{CodecError, Error, VPXResult as Result, check_ctx_err};
+use image::Image;
+
+/// An encoder which produces several streams of different resolutions from the same input. The
+/// streams share the motion analysis (`vpx_codec_enc_init_multi()`), which is much cheaper than
+/// running independent encoders. Only VP8 supports this mode and libvpx has to be built with
+/// `--enable-multi-res-encoding`.
+pub struct SimulcastEncoder<Enc: VpxEncoder> {
+    contexts: Vec<ffi::vpx_codec_ctx_t>,
+    // libvpx keeps the pointers to the configurations, so they must live as long as the encoder.
+    configs: Vec<ffi::vpx_codec_enc_cfg_t>,
+    _phantom: PhantomData<Enc>,
+}
+
+impl SimulcastEncoder<VP8> {
+    /// Creates a new encoder with one stream per configuration. The configurations have to be
+    /// ordered from the highest resolution to the lowest one; the down-sampling factors between
+    /// the streams are derived from the configured frame sizes, which must scale both dimensions
+    /// by the same ratio. The `cpu_used` and the content type of each configuration are applied to
+    /// its stream.
+    pub fn new(configs: Vec<EncoderConfig<VP8>>, flags: Option<CodecFlags>) -> Result<Self> {
+        if configs.is_empty() {
+            return Err(Error::with_detail(CodecError::InvalidParam,
+                                          "vpx_codec_enc_init_multi",
+                                          "no stream configurations given".to_string()));
+        }
+        let controls: Vec<_> = configs.iter()
+                                      .map(|config| (config.cpu_used(), config.content_type()))
+                                      .collect();
+        let mut configs: Vec<ffi::vpx_codec_enc_cfg_t> =
+            configs.into_iter().map(|config| config.into()).collect();
+
+        let mut factors = Vec::with_capacity(configs.len());
+        for pair in configs.windows(2) {
+            if pair[1].g_w > pair[0].g_w || pair[1].g_h > pair[0].g_h || pair[1].g_w == 0 {
+                return Err(Error::with_detail(
+                    CodecError::InvalidParam,
+                    "vpx_codec_enc_init_multi",
+                    format!("stream {}x{} follows a smaller stream {}x{}",
+                            pair[1].g_w, pair[1].g_h, pair[0].g_w, pair[0].g_h)));
+            }
+            if pair[0].g_w as u64 * pair[1].g_h as u64 != pair[1].g_w as u64 * pair[0].g_h as u64 {
+                return Err(Error::with_detail(
+                    CodecError::InvalidParam,
+                    "vpx_codec_enc_init_multi",
+                    format!("stream {}x{} is not scaled proportionally to the stream {}x{}",
+                            pair[1].g_w, pair[1].g_h, pair[0].g_w, pair[0].g_h)));
+            }
+            factors.push(down_sampling_factor(pair[0].g_w, pair[1].g_w));
+        }
+        factors.push(ffi::vpx_rational_t { num: 1, den: 1 });
+
+        let flags = flags.unwrap_or(CodecFlags::default());
+        flags.validate_for_encoder(&capabilities::<VP8>())?;
+
+        let mut contexts: Vec<ffi::vpx_codec_ctx_t> =
+            configs.iter().map(|_| Default::default()).collect();
+        let res = unsafe { ffi::vpx_codec_enc_init_multi_ver(contexts.as_mut_ptr(),
+                                                             VP8::interface(),
+                                                             configs.as_mut_ptr(),
+                                                             configs.len() as libc::c_int,
+                                                             flags.into(),
+                                                             factors.as_mut_ptr(),
+                                                             ffi::VPX_ENCODER_ABI_VERSION
+                                                                 as i32) };
+        // libvpx destroys all the contexts itself when the initialization fails.
+        check_ctx_err(failed_context(&mut contexts), res, "vpx_codec_enc_init_multi")?;
+
+        let mut encoder = SimulcastEncoder {
+            contexts: contexts,
+            configs: configs,
+            _phantom: PhantomData { },
+        };
+        for (ctx, (cpu_used, content_type)) in encoder.contexts.iter_mut().zip(controls) {
+            if let Some(cpu_used) = cpu_used {
+                let (min, max) = VP8::cpu_used_range();
+                check_range("VP8E_SET_CPUUSED", cpu_used, min, max)?;
+                control(ctx, ffi::vp8e_enc_control_id::VP8E_SET_CPUUSED, cpu_used,
+                        "VP8E_SET_CPUUSED")?;
+            }
+            if let Some(content_type) = content_type {
+                control(ctx,
+                        ffi::vp8e_enc_control_id::VP8E_SET_SCREEN_CONTENT_MODE,
+                        screen_content_mode(content_type)?,
+                        "VP8E_SET_SCREEN_CONTENT_MODE")?;
+            }
+        }
+        Ok(encoder)
+    }
+
+    pub fn number_of_streams(&self) -> usize {
+        self.contexts.len()
+    }
+
+    /// Encodes a single frame in all resolutions. The image must have the dimensions of the first
+    /// stream, the lower resolutions are produced by scaling it (see `Image::scaled()`).
+    pub fn encode(&mut self,
+                  image: &Image,
+                  pts: ffi::vpx_codec_pts_t,
+                  duration: u64,
+                  flags: FrameFlags,
+                  deadline: Deadline)
+        -> Result<()>
+    {
+        if image.d_w != self.configs[0].g_w || image.d_h != self.configs[0].g_h {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "vpx_codec_encode",
+                format!("image {}x{} does not match the stream {}x{}",
+                        image.d_w, image.d_h, self.configs[0].g_w, self.configs[0].g_h)));
+        }
+
+        let scaled = self.configs[1..].iter()
+                                      .map(|config| image.scaled(config.g_w, config.g_h))
+                                      .collect::<Result<Vec<_>>>()?;
+        // libvpx expects an array of images, one per stream.
+        let mut images = vec![*image.deref()];
+        images.extend(scaled.iter().map(|image| *image.deref()));
+
+        let res = unsafe { ffi::vpx_codec_encode(&mut self.contexts[0],
+                                                 images.as_ptr(),
+                                                 pts,
+                                                 duration,
+                                                 flags.into(),
+                                                 deadline.into()) };
+        check_ctx_err(failed_context(&mut self.contexts), res, "vpx_codec_encode")?;
+        Ok(())
+    }
+
+    /// Returns an iterator over the frames encoded so far in all streams, each frame is tagged
+    /// with the index of its stream (in the order of the configurations).
+    pub fn frames_iter(&mut self) -> SimulcastFramesIter {
+        SimulcastFramesIter {
+            streams: self.contexts.iter_mut().zip(self.configs.iter()).map(|(ctx, config)| {
+                FramesIter::new(ctx, LayerId::default(), vec![(config.g_w, config.g_h)])
+            }).collect(),
+            current: 0,
+        }
+    }
+}
+
+impl<Enc: VpxEncoder> Drop for SimulcastEncoder<Enc> {
+    fn drop(&mut self) {
+        // Flush all the streams at once, see `Encoder::drop()`.
+        let res = unsafe {
+            ffi::vpx_codec_encode(&mut self.contexts[0], null(), -1, 1, 0,
+                                  Deadline::GoodQuality.into())
+        };
+        check_ctx_err(failed_context(&mut self.contexts), res, "vpx_codec_encode")
+            .expect("Could not release the encoder resource");
+        for ctx in &mut self.contexts {
+            for _ in FramesIter::new(ctx, LayerId::default(), Vec::new()) {
+            }
+        }
+        for ctx in &mut self.contexts {
+            unsafe { ffi::vpx_codec_destroy(ctx) };
+        }
+    }
+}
+
+/// Iterates over the encoded data of all streams of a `SimulcastEncoder`, yielding the index of
+/// the stream together with each packet.
+pub struct SimulcastFramesIter<'encoder> {
+    streams: Vec<FramesIter<'encoder>>,
+    current: usize,
+}
+
+impl<'encoder> Iterator for SimulcastFramesIter<'encoder> {
+    type Item = (usize, PacketKind<'encoder>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.current < self.streams.len() {
+            if let Some(packet) = self.streams[self.current].next() {
+                return Some((self.current, packet));
+            }
+            self.current += 1;
+        }
+        None
+    }
+}
+
+/// Sets a codec control of a single stream (`vpx_codec_control()`).
+fn control(ctx: &mut ffi::vpx_codec_ctx_t,
+           id: ffi::vp8e_enc_control_id,
+           value: libc::c_int,
+           operation: &'static str) -> Result<()>
+{
+    let res = unsafe { ffi::vpx_codec_control_(ctx, id as libc::c_int, value) };
+    check_ctx_err(ctx, res, operation)?;
+    Ok(())
+}
+
+/// Returns the context of the stream which has failed the last operation. libvpx stores the error
+/// in the context of the stream being processed, the first one is used if none reports it.
+fn failed_context(contexts: &mut [ffi::vpx_codec_ctx_t]) -> &mut ffi::vpx_codec_ctx_t {
+    let index = contexts.iter().position(|ctx| ctx.err != ffi::VPX_CODEC_OK).unwrap_or(0);
+    &mut contexts[index]
+}
+
+/// Returns the ratio between two frame widths as a reduced fraction.
+fn down_sampling_factor(higher: u32, lower: u32) -> ffi::vpx_rational_t {
+    let (mut a, mut b) = (higher, lower);
+    while b != 0 {
+        let rem = a % b;
+        a = b;
+        b = rem;
+    }
+    ffi::vpx_rational_t {
+        num: (higher / a) as i32,
+        den: (lower / a) as i32,
+    }
+}
@@ -0,0 +1,326 @@
+//! Statistics of the encoder, suitable for monitoring and adaptive bitrate logic.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration as StdDuration;
+
+use ffi;
+use libc;
+
+use encoder::{Deadline, Encoder, LayerId, VpxEncoder};
+use error::VPXResult as Result;
+
+/// Aggregated statistics of an `Encoder`, see `Encoder::stats()`. The output related values,
+/// including the quantizers, are only updated when the frames are obtained with
+/// `Encoder::frames_iter()`. libvpx only reports the quantizer of the frame encoded last, so the
+/// quantizers are only recorded if the encoder does not delay the frames (`g_lag_in_frames` is 0).
+#[derive(Clone, Debug)]
+pub struct EncoderStats {
+    frames_in: u64,
+    frames_out: u64,
+    keyframes: u64,
+    bytes_per_layer: HashMap<LayerId, u64>,
+    quantizer_sum: u64,
+    quantizer_samples: u64,
+    last_quantizer: Option<i32>,
+    // Timestamp and quantizer of the frame encoded by the last `encode()` call, which is recorded
+    // once the frame is produced (the rate control may drop it).
+    encoded_quantizer: Option<(i64, i32)>,
+    // Timebase of the timestamps as (numerator, denominator).
+    timebase: (i64, i64),
+    bitrate_window: StdDuration,
+    // Timestamp, duration and size of the recent output frames.
+    recent_frames: VecDeque<(i64, u64, usize)>,
+    // Number of encode calls and their total duration per kind of the deadline.
+    latency: [(u32, StdDuration); 4],
+}
+
+impl EncoderStats {
+    /// Creates empty statistics for the timestamps in the given timebase. The running bitrate is
+    /// calculated over one second.
+    pub fn new(timebase_num: i32, timebase_den: i32) -> Self {
+        EncoderStats {
+            frames_in: 0,
+            frames_out: 0,
+            keyframes: 0,
+            bytes_per_layer: HashMap::new(),
+            quantizer_sum: 0,
+            quantizer_samples: 0,
+            last_quantizer: None,
+            encoded_quantizer: None,
+            timebase: (timebase_num as i64, timebase_den as i64),
+            bitrate_window: StdDuration::from_secs(1),
+            recent_frames: VecDeque::new(),
+            latency: [(0, StdDuration::from_secs(0)); 4],
+        }
+    }
+
+    /// Sets the time window the running bitrate is calculated over.
+    pub fn set_bitrate_window(&mut self, window: StdDuration) {
+        self.bitrate_window = window;
+    }
+
+    /// The number of frames passed to the encoder.
+    pub fn frames_in(&self) -> u64 {
+        self.frames_in
+    }
+
+    /// The number of frames produced by the encoder (each superframe and each set of partitions
+    /// counts as a single frame).
+    pub fn frames_out(&self) -> u64 {
+        self.frames_out
+    }
+
+    /// The number of frames which have been passed to the encoder, but not produced. Besides the
+    /// frames dropped by the rate control, it includes the frames delayed by the encoder if
+    /// `g_lag_in_frames` is not 0.
+    pub fn dropped_frames(&self) -> u64 {
+        self.frames_in.saturating_sub(self.frames_out)
+    }
+
+    pub fn keyframes(&self) -> u64 {
+        self.keyframes
+    }
+
+    /// The total number of bytes produced for each layer.
+    pub fn bytes_per_layer(&self) -> &HashMap<LayerId, u64> {
+        &self.bytes_per_layer
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes_per_layer.values().sum()
+    }
+
+    /// The bitrate (in bits per second) of the frames produced within the last bitrate window.
+    pub fn bitrate(&self) -> Option<f64> {
+        let first = self.recent_frames.front()?;
+        let last = self.recent_frames.back()?;
+        let span = self.seconds(last.0 + last.1 as i64 - first.0);
+        if span <= 0.0 {
+            return None;
+        }
+        let bytes: usize = self.recent_frames.iter().map(|&(_, _, size)| size).sum();
+        Some(bytes as f64 * 8.0 / span)
+    }
+
+    /// The quantizer (in the range 0..63) of the last encoded frame.
+    pub fn last_quantizer(&self) -> Option<i32> {
+        self.last_quantizer
+    }
+
+    /// The average quantizer (in the range 0..63) of all encoded frames.
+    pub fn average_quantizer(&self) -> Option<f64> {
+        if self.quantizer_samples == 0 {
+            None
+        } else {
+            Some(self.quantizer_sum as f64 / self.quantizer_samples as f64)
+        }
+    }
+
+    /// The average duration of the `Encoder::encode()` calls with the given kind of deadline. All
+    /// `Deadline::Custom` deadlines are aggregated together.
+    pub fn encode_latency(&self, deadline: &Deadline) -> Option<StdDuration> {
+        let (count, total) = self.latency[deadline_index(deadline)];
+        if count == 0 {
+            None
+        } else {
+            Some(total / count)
+        }
+    }
+
+    /// Records an `Encoder::encode()` call.
+    pub(crate) fn record_input(&mut self, deadline: &Deadline, latency: StdDuration) {
+        self.frames_in += 1;
+        let entry = &mut self.latency[deadline_index(deadline)];
+        entry.0 += 1;
+        entry.1 += latency;
+    }
+
+    /// Records the quantizer of the frame with the given timestamp, which has just been encoded.
+    pub(crate) fn record_encoded_quantizer(&mut self, pts: i64, quantizer: i32) {
+        self.encoded_quantizer = Some((pts, quantizer));
+    }
+
+    /// Records the quantizer of a produced frame.
+    pub(crate) fn record_quantizer(&mut self, quantizer: i32) {
+        self.last_quantizer = Some(quantizer);
+        self.quantizer_sum += quantizer as u64;
+        self.quantizer_samples += 1;
+    }
+
+    /// Records a frame produced by the encoder.
+    pub(crate) fn record_output(&mut self, pts: i64, duration: u64, size: usize, keyframe: bool) {
+        self.frames_out += 1;
+        if let Some((quantizer_pts, quantizer)) = self.encoded_quantizer {
+            if quantizer_pts == pts {
+                self.encoded_quantizer = None;
+                self.record_quantizer(quantizer);
+            }
+        }
+        if keyframe {
+            self.keyframes += 1;
+        }
+
+        self.recent_frames.push_back((pts, duration, size));
+        while let Some(&(first_pts, _, _)) = self.recent_frames.front() {
+            if self.seconds(pts - first_pts) < self.window_seconds() {
+                break;
+            }
+            self.recent_frames.pop_front();
+        }
+    }
+
+    /// Records the data produced for the layer.
+    pub(crate) fn record_layer(&mut self, layer_id: LayerId, size: usize) {
+        *self.bytes_per_layer.entry(layer_id).or_insert(0) += size as u64;
+    }
+
+    fn seconds(&self, ticks: i64) -> f64 {
+        if self.timebase.1 == 0 {
+            return 0.0;
+        }
+        ticks as f64 * self.timebase.0 as f64 / self.timebase.1 as f64
+    }
+
+    fn window_seconds(&self) -> f64 {
+        self.bitrate_window.as_secs() as f64 +
+            self.bitrate_window.subsec_nanos() as f64 / 1_000_000_000.0
+    }
+}
+
+/// Queries the quantizer (in the range 0..63) of the last encoded frame, `None` if the encoder
+/// cannot report it.
+pub(crate) fn query_quantizer(ctx: &mut ffi::vpx_codec_ctx_t) -> Option<i32> {
+    let mut quantizer: i32 = 0;
+    let res = unsafe {
+        ffi::vpx_codec_control_(ctx,
+                                ffi::vp8e_enc_control_id::VP8E_GET_LAST_QUANTIZER_64
+                                    as libc::c_int,
+                                &mut quantizer as *mut i32)
+    };
+    if res == ffi::VPX_CODEC_OK {
+        Some(quantizer)
+    } else {
+        None
+    }
+}
+
+fn deadline_index(deadline: &Deadline) -> usize {
+    match *deadline {
+        Deadline::Realtime => 0,
+        Deadline::GoodQuality => 1,
+        Deadline::BestQuality => 2,
+        Deadline::Custom(_) => 3,
+    }
+}
+
+impl<Enc: VpxEncoder> Encoder<Enc> {
+    /// Returns the quantizer of the last encoded frame in the internal scale of the codec, i.e.
+    /// 0..127 for VP8 and 0..255 for VP9 (`VP8E_GET_LAST_QUANTIZER`).
+    pub fn last_quantizer(&mut self) -> Result<i32> {
+        let mut quantizer: i32 = 0;
+        self.control_ptr(ffi::vp8e_enc_control_id::VP8E_GET_LAST_QUANTIZER,
+                         &mut quantizer,
+                         "VP8E_GET_LAST_QUANTIZER")?;
+        Ok(quantizer)
+    }
+
+    /// Returns the quantizer of the last encoded frame in the range 0..63, the same scale as of
+    /// the quantizer limits in the configuration (`VP8E_GET_LAST_QUANTIZER_64`).
+    pub fn last_quantizer_64(&mut self) -> Result<i32> {
+        let mut quantizer: i32 = 0;
+        self.control_ptr(ffi::vp8e_enc_control_id::VP8E_GET_LAST_QUANTIZER_64,
+                         &mut quantizer,
+                         "VP8E_GET_LAST_QUANTIZER_64")?;
+        Ok(quantizer)
+    }
+
+    pub fn stats(&self) -> &EncoderStats {
+        &self.stats
+    }
+
+    /// Resets the statistics, e.g. after they have been reported.
+    pub fn reset_stats(&mut self) {
+        let mut stats = EncoderStats::new(self.config.g_timebase.num, self.config.g_timebase.den);
+        stats.set_bitrate_window(self.stats.bitrate_window);
+        self.stats = stats;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration as StdDuration;
+
+    use time::Duration;
+
+    use encoder::Deadline;
+    use super::EncoderStats;
+
+    #[test]
+    fn bitrate_window() {
+        // Millisecond timestamps, 1 second window.
+        let mut stats = EncoderStats::new(1, 1000);
+        assert_eq!(stats.bitrate(), None);
+
+        for pts in 0..4 {
+            stats.record_output(pts * 500, 500, 1000, pts == 0);
+        }
+        // Only the frames at 1000ms and 1500ms are within the window.
+        assert_eq!(stats.bitrate(), Some(16000.0));
+
+        stats.set_bitrate_window(StdDuration::from_secs(3));
+        stats.record_output(2000, 500, 1000, false);
+        assert_eq!(stats.bitrate(), Some(16000.0));
+        assert_eq!(stats.frames_out(), 5);
+        assert_eq!(stats.keyframes(), 1);
+    }
+
+    #[test]
+    fn dropped_frames() {
+        let mut stats = EncoderStats::new(1, 30);
+        for _ in 0..3 {
+            stats.record_input(&Deadline::Realtime, StdDuration::from_millis(1));
+        }
+        stats.record_output(0, 1, 100, true);
+        assert_eq!(stats.frames_in(), 3);
+        assert_eq!(stats.dropped_frames(), 2);
+
+        // The frames flushed at the end are not counted as input.
+        for pts in 1..4 {
+            stats.record_output(pts, 1, 100, false);
+        }
+        assert_eq!(stats.dropped_frames(), 0);
+    }
+
+    #[test]
+    fn quantizers_follow_the_timestamps() {
+        let mut stats = EncoderStats::new(1, 30);
+        stats.record_encoded_quantizer(0, 20);
+        stats.record_output(0, 1, 100, true);
+        assert_eq!(stats.last_quantizer(), Some(20));
+
+        // The frame 1 has been dropped, the quantizer reported for it is not recorded.
+        stats.record_encoded_quantizer(1, 20);
+        stats.record_encoded_quantizer(2, 30);
+        stats.record_output(2, 1, 100, false);
+        stats.record_output(2, 1, 100, false);
+        assert_eq!(stats.last_quantizer(), Some(30));
+        assert_eq!(stats.average_quantizer(), Some(25.0));
+    }
+
+    #[test]
+    fn latency_buckets() {
+        let mut stats = EncoderStats::new(1, 30);
+        stats.record_input(&Deadline::Realtime, StdDuration::from_millis(10));
+        stats.record_input(&Deadline::Realtime, StdDuration::from_millis(30));
+        stats.record_input(&Deadline::Custom(Duration::milliseconds(5)),
+                           StdDuration::from_millis(4));
+        stats.record_input(&Deadline::Custom(Duration::milliseconds(50)),
+                           StdDuration::from_millis(8));
+
+        assert_eq!(stats.encode_latency(&Deadline::Realtime), Some(StdDuration::from_millis(20)));
+        assert_eq!(stats.encode_latency(&Deadline::GoodQuality), None);
+        assert_eq!(stats.encode_latency(&Deadline::BestQuality), None);
+        assert_eq!(stats.encode_latency(&Deadline::Custom(Duration::milliseconds(1))),
+                   Some(StdDuration::from_millis(6)));
+    }
+}
@@ -0,0 +1,186 @@
+//! Spatial scalability (SVC) parameters for the VP9 encoder.
+
+use ffi;
+
+use encoder::ReferenceFlags;
+use error::{CodecError, Error, VPXResult as Result};
+
+/// The number of reference frame buffers available to the VP9 encoder.
+pub const REFERENCE_BUFFERS: u32 = 8;
+
+/// Parameters of the spatial layers of the VP9 SVC encoder, wraps `vpx_svc_extra_cfg_t`. The
+/// layers are added from the lowest resolution to the highest one.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SvcParameters {
+    layers: Vec<SpatialLayer>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct SpatialLayer {
+    scaling_num: u32,
+    scaling_den: u32,
+    min_quantizer: u32,
+    max_quantizer: u32,
+}
+
+impl SvcParameters {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a spatial layer which is scaled by `scaling_num / scaling_den` relative to the
+    /// configured frame dimensions (e.g. `1 / 4`, `1 / 2` and `1 / 1` for a typical three-layer
+    /// setup) and uses the given quantizer range (0-63) for all its temporal layers.
+    pub fn add_spatial_layer(&mut self,
+                             scaling_num: u32,
+                             scaling_den: u32,
+                             min_quantizer: u32,
+                             max_quantizer: u32)
+    {
+        self.layers.push(SpatialLayer {
+            scaling_num: scaling_num,
+            scaling_den: scaling_den,
+            min_quantizer: min_quantizer,
+            max_quantizer: max_quantizer,
+        });
+    }
+
+    pub fn number_of_layers(&self) -> u32 {
+        self.layers.len() as u32
+    }
+
+    /// Returns the scaling factor of each spatial layer as `(numerator, denominator)`.
+    pub fn scaling_factors(&self) -> Vec<(u32, u32)> {
+        self.layers.iter().map(|layer| (layer.scaling_num, layer.scaling_den)).collect()
+    }
+
+    /// Converts the parameters to the libvpx representation. The quantizers are set per layer
+    /// (spatial and temporal), so the number of the temporal layers has to be known.
+    pub fn to_ffi(&self, temporal_layers: u32) -> Result<ffi::vpx_svc_extra_cfg_t> {
+        let mut params: ffi::vpx_svc_extra_cfg_t = Default::default();
+        if self.layers.len() > params.scaling_factor_num.len() {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "VP9E_SET_SVC_PARAMETERS",
+                format!("{} spatial layers exceed the maximum of {} layers",
+                        self.layers.len(), params.scaling_factor_num.len())));
+        }
+        let temporal_layers = temporal_layers.max(1) as usize;
+        if self.layers.len() * temporal_layers > ffi::VPX_MAX_LAYERS as usize {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "VP9E_SET_SVC_PARAMETERS",
+                format!("{} spatial and {} temporal layers exceed the maximum of {} layers",
+                        self.layers.len(), temporal_layers, ffi::VPX_MAX_LAYERS)));
+        }
+
+        for (sl, layer) in self.layers.iter().enumerate() {
+            params.scaling_factor_num[sl] = layer.scaling_num as i32;
+            params.scaling_factor_den[sl] = layer.scaling_den as i32;
+            for tl in 0..temporal_layers {
+                let index = sl * temporal_layers + tl;
+                params.min_quantizers[index] = layer.min_quantizer as i32;
+                params.max_quantizers[index] = layer.max_quantizer as i32;
+            }
+        }
+        Ok(params)
+    }
+}
+
+/// Reference frame configuration of the spatial layers of the VP9 SVC encoder for the next frame,
+/// wraps `vpx_svc_ref_frame_config_t`. Each spatial layer specifies which of the reference frame
+/// buffers are used as its last, golden and alternate reference frames and the flags controlling
+/// whether these references are used for prediction and updated after the encoding. This allows
+/// implementing arbitrary prediction structures (e.g. full SVC or k-SVC) and requires the encoder
+/// to be configured with the bypass layering mode, otherwise libvpx ignores the configuration.
+/// This is the case when more than one temporal layer is configured with
+/// `EncoderConfig::set_temporal_layering()`, the spatial-only SVC has to call
+/// `EncoderConfig::set_bypass_layering()`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SvcRefFrameConfig {
+    layers: Vec<LayerReferences>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct LayerReferences {
+    flags: ReferenceFlags,
+    last: u32,
+    golden: u32,
+    altref: u32,
+}
+
+impl SvcRefFrameConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds the reference configuration of the next spatial layer (starting from the lowest
+    /// one). `last`, `golden` and `altref` are the indices of the buffers (from 0 to
+    /// `REFERENCE_BUFFERS - 1`) used as the corresponding reference frames.
+    pub fn add_spatial_layer(&mut self,
+                             flags: ReferenceFlags,
+                             last: u32,
+                             golden: u32,
+                             altref: u32)
+    {
+        self.layers.push(LayerReferences {
+            flags: flags,
+            last: last,
+            golden: golden,
+            altref: altref,
+        });
+    }
+
+    pub fn number_of_layers(&self) -> u32 {
+        self.layers.len() as u32
+    }
+
+    /// Converts the configuration to the libvpx representation, validating the buffer indices
+    /// and the number of the layers. The layout is the one of libvpx 1.8 and newer, where the
+    /// references are enabled per layer and the updated buffers are given as a bit mask of the
+    /// buffer slots. The entropy flag of `ReferenceFlags` has no equivalent in this structure and
+    /// has to be passed with the frame flags of `Encoder::encode()` instead.
+    pub fn to_ffi(&self) -> Result<ffi::vpx_svc_ref_frame_config_t> {
+        let mut config: ffi::vpx_svc_ref_frame_config_t = Default::default();
+        if self.layers.len() > config.update_buffer_slot.len() {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "VP9E_SET_SVC_REF_FRAME_CONFIG",
+                format!("{} spatial layers exceed the maximum of {} layers",
+                        self.layers.len(), config.update_buffer_slot.len())));
+        }
+
+        for (sl, layer) in self.layers.iter().enumerate() {
+            let indices = [layer.last, layer.golden, layer.altref];
+            if let Some(index) = indices.iter().find(|&&index| index >= REFERENCE_BUFFERS) {
+                return Err(Error::with_detail(
+                    CodecError::InvalidParam,
+                    "VP9E_SET_SVC_REF_FRAME_CONFIG",
+                    format!("buffer index {} of spatial layer {} is out of range", index, sl)));
+            }
+
+            let flags: ffi::vpx_enc_frame_flags_t = layer.flags.into();
+            let is_set = |flag: u32| flags & flag as ffi::vpx_enc_frame_flags_t != 0;
+
+            config.lst_fb_idx[sl] = layer.last as i32;
+            config.gld_fb_idx[sl] = layer.golden as i32;
+            config.alt_fb_idx[sl] = layer.altref as i32;
+            config.reference_last[sl] = !is_set(ffi::VP8_EFLAG_NO_REF_LAST) as i32;
+            config.reference_golden[sl] = !is_set(ffi::VP8_EFLAG_NO_REF_GF) as i32;
+            config.reference_alt_ref[sl] = !is_set(ffi::VP8_EFLAG_NO_REF_ARF) as i32;
+
+            let mut update = 0;
+            if !is_set(ffi::VP8_EFLAG_NO_UPD_LAST) {
+                update |= 1 << layer.last;
+            }
+            if !is_set(ffi::VP8_EFLAG_NO_UPD_GF) {
+                update |= 1 << layer.golden;
+            }
+            if !is_set(ffi::VP8_EFLAG_NO_UPD_ARF) {
+                update |= 1 << layer.altref;
+            }
+            config.update_buffer_slot[sl] = update;
+        }
+        Ok(config)
+    }
+}
@@ -0,0 +1,133 @@
+//! Temporal scalability (temporal layers) support for VP8 and VP9 encoders.
+
+use std::marker::PhantomData;
+
+use encoder::{ReferenceFlags, VpxEncoder};
+
+/// A temporal layering pattern. Each frame of the pattern belongs to a particular temporal layer
+/// and only references the frames of the same or lower layers, so the higher layers can be
+/// dropped (e.g. by an SFU) to get a stream with a lower frame rate.
+///
+/// The built-in patterns correspond to the modes of the `vpx_temporal_svc_encoder` example from
+/// libvpx. The pattern has to be applied to the configuration with
+/// `EncoderConfig::set_temporal_layering()`, then `next_frame()` should be called for every frame
+/// to get the frame flags and the layer id which has to be passed to
+/// `Encoder::set_temporal_layer_id()` before the frame is encoded.
+pub struct TemporalLayering<Enc> {
+    /// The temporal layer for each frame of the pattern.
+    layer_ids: Vec<u32>,
+    /// The reference buffer usage for each frame of the pattern.
+    references: Vec<ReferenceFlags>,
+    /// The frame rate decimation factor of each layer.
+    rate_decimators: Vec<u32>,
+    /// Cumulative share of the target bitrate (in percents) used by each layer.
+    bitrate_shares: Vec<u32>,
+    frame_index: usize,
+    _phantom: PhantomData<Enc>,
+}
+
+impl<Enc: VpxEncoder> TemporalLayering<Enc> {
+    /// A single layer which only uses the last frame for prediction.
+    pub fn one_layer() -> Self {
+        TemporalLayering {
+            layer_ids: vec![0],
+            references: vec![base_layer_references()],
+            rate_decimators: vec![1],
+            bitrate_shares: vec![100],
+            frame_index: 0,
+            _phantom: PhantomData { },
+        }
+    }
+
+    /// Two layers with a period of 2 frames: `0, 1, 0, 1, ...`. The base layer gets half of the
+    /// frame rate and 60% of the bitrate.
+    pub fn two_layers() -> Self {
+        let mut enhancement = ReferenceFlags::default();
+        enhancement.no_ref_altref(true);
+        enhancement.no_update_last(true);
+        enhancement.no_update_altref(true);
+
+        TemporalLayering {
+            layer_ids: vec![0, 1],
+            references: vec![base_layer_references(), enhancement],
+            rate_decimators: vec![2, 1],
+            bitrate_shares: vec![60, 100],
+            frame_index: 0,
+            _phantom: PhantomData { },
+        }
+    }
+
+    /// Three layers with a period of 4 frames: `0, 2, 1, 2, ...`. The base layer gets a quarter
+    /// of the frame rate and 40% of the bitrate, the first two layers get half of the frame rate
+    /// and 60% of the bitrate.
+    pub fn three_layers() -> Self {
+        let mut middle = ReferenceFlags::default();
+        middle.no_ref_golden(true);
+        middle.no_ref_altref(true);
+        middle.no_update_last(true);
+        middle.no_update_altref(true);
+
+        let mut top = ReferenceFlags::default();
+        top.no_ref_altref(true);
+        top.no_update_last(true);
+        top.no_update_golden(true);
+        top.no_update_altref(true);
+        top.no_update_entropy(true);
+
+        TemporalLayering {
+            layer_ids: vec![0, 2, 1, 2],
+            references: vec![base_layer_references(), top, middle, top],
+            rate_decimators: vec![4, 2, 1],
+            bitrate_shares: vec![40, 60, 100],
+            frame_index: 0,
+            _phantom: PhantomData { },
+        }
+    }
+
+    pub fn number_of_layers(&self) -> u32 {
+        self.rate_decimators.len() as u32
+    }
+
+    /// The number of frames after which the pattern repeats.
+    pub fn periodicity(&self) -> u32 {
+        self.layer_ids.len() as u32
+    }
+
+    pub fn layer_ids(&self) -> &[u32] {
+        &self.layer_ids
+    }
+
+    pub fn rate_decimators(&self) -> &[u32] {
+        &self.rate_decimators
+    }
+
+    /// Returns the cumulative target bitrate of each layer for the given total target bitrate.
+    pub fn layer_bitrates(&self, target_bitrate: u32) -> Vec<u32> {
+        self.bitrate_shares.iter()
+                           .map(|share| (target_bitrate as u64 * *share as u64 / 100) as u32)
+                           .collect()
+    }
+
+    /// Returns the frame flags and the temporal layer id for the next frame and advances the
+    /// pattern.
+    pub fn next_frame(&mut self) -> (Enc::FrameFlags, u32) {
+        let index = self.frame_index % self.layer_ids.len();
+        self.frame_index = index + 1;
+        (Enc::FrameFlags::from(self.references[index]), self.layer_ids[index])
+    }
+
+    /// Restarts the pattern from the base layer, e.g. after a keyframe has been forced.
+    pub fn reset(&mut self) {
+        self.frame_index = 0;
+    }
+}
+
+/// The base layer only references and updates the last frame.
+fn base_layer_references() -> ReferenceFlags {
+    let mut references = ReferenceFlags::default();
+    references.no_ref_golden(true);
+    references.no_ref_altref(true);
+    references.no_update_golden(true);
+    references.no_update_altref(true);
+    references
+}
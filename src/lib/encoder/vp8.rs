@@ -1,6 +1,9 @@
 use ffi;
 
-use encoder::VpxEncoder;
+use encoder::header::FrameSizeParser;
+use encoder::{ContentType, Encoder, ReferenceFlags, VpxEncoder};
+use error::{CodecError, Error, VPXResult as Result};
+use image::{ColorRange, ColorSpace};
 
 pub struct VP8;
 
@@ -10,6 +13,46 @@ impl VpxEncoder for VP8 {
     fn interface() -> *mut ffi::vpx_codec_iface_t {
         unsafe { &mut ffi::vpx_codec_vp8_cx_algo }
     }
+
+    fn cpu_used_range() -> (i32, i32) {
+        (-16, 16)
+    }
+
+    fn roi_block_size() -> u32 {
+        16
+    }
+
+    fn screen_share_cpu_used() -> i32 {
+        -6
+    }
+
+    fn frame_size_parser() -> Option<FrameSizeParser> {
+        Some(FrameSizeParser::vp8())
+    }
+
+    fn set_content_type(encoder: &mut Encoder<Self>, content_type: ContentType) -> Result<()> {
+        encoder.control(ffi::vp8e_enc_control_id::VP8E_SET_SCREEN_CONTENT_MODE,
+                        screen_content_mode(content_type)?,
+                        "VP8E_SET_SCREEN_CONTENT_MODE")
+    }
+
+    fn signal_color(_encoder: &mut Encoder<Self>, _space: ColorSpace, _range: ColorRange)
+        -> Result<()>
+    {
+        // VP8 streams carry no color metadata.
+        Ok(())
+    }
+
+    fn enable_layers(_encoder: &mut Encoder<Self>) -> Result<()> {
+        // VP8 enables the temporal layers from the configuration itself.
+        Ok(())
+    }
+
+    fn set_temporal_layer_id(encoder: &mut Encoder<Self>, layer_id: u32) -> Result<()> {
+        encoder.control(ffi::vp8e_enc_control_id::VP8E_SET_TEMPORAL_LAYER_ID,
+                        layer_id as i32,
+                        "VP8E_SET_TEMPORAL_LAYER_ID")
+    }
 }
 
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
@@ -17,52 +60,48 @@ pub struct FrameFlags {
     force_kf: bool,
 
     // VP8 specific flags.
-    no_ref_last: bool,
-    no_ref_gf: bool,
-    no_ref_arf: bool,
-    no_upd_last: bool,
-    no_upd_gf: bool,
-    no_upd_arf: bool,
+    references: ReferenceFlags,
     force_gf: bool,
     force_arf: bool,
-    no_upd_entropy: bool,
 }
 
 impl FrameFlags {
     pub fn keyframe(&mut self, keyframe: bool) {
         self.force_kf = keyframe;
     }
+
+    pub fn references(&mut self, references: ReferenceFlags) {
+        self.references = references;
+    }
+}
+
+impl From<ReferenceFlags> for FrameFlags {
+    fn from(references: ReferenceFlags) -> Self {
+        FrameFlags {
+            references: references,
+            ..Default::default()
+        }
+    }
 }
 
 impl Into<ffi::vpx_enc_frame_flags_t> for FrameFlags {
     fn into(self) -> ffi::vpx_enc_frame_flags_t {
-        let mut flags: ffi::vpx_enc_frame_flags_t = 0;
+        let mut flags: ffi::vpx_enc_frame_flags_t = self.references.into();
         if self.force_kf {
             flags |= ffi::VPX_EFLAG_FORCE_KF as i64;
         }
-        if self.no_ref_last {
-            flags |= ffi::VP8_EFLAG_NO_REF_LAST as i64;
-        }
-        if self.no_ref_gf {
-            flags |= ffi::VP8_EFLAG_NO_REF_GF as i64;
-        }
-        if self.no_ref_arf {
-            flags |= ffi::VP8_EFLAG_NO_REF_ARF as i64;
-        }
-        if self.no_upd_last {
-            flags |= ffi::VP8_EFLAG_NO_UPD_LAST as i64;
-        }
-        if self.no_upd_gf {
-            flags |= ffi::VP8_EFLAG_NO_UPD_GF as i64;
-        }
-        if self.no_upd_arf {
-            flags |= ffi::VP8_EFLAG_NO_UPD_ARF as i64;
-        }
-        if self.no_upd_entropy {
-            flags |= ffi::VP8_EFLAG_NO_UPD_ENTROPY as i64;
-        }
         flags
     }
 }
 
-
+/// Returns the value of `VP8E_SET_SCREEN_CONTENT_MODE` for the given type of content.
+pub(crate) fn screen_content_mode(content_type: ContentType) -> Result<i32> {
+    match content_type {
+        ContentType::Default => Ok(0),
+        ContentType::Screen => Ok(1),
+        ContentType::Film => Err(Error::with_detail(CodecError::Incapable,
+                                                    "VP8E_SET_SCREEN_CONTENT_MODE",
+                                                    "VP8 cannot be tuned for film content"
+                                                        .to_string())),
+    }
+}
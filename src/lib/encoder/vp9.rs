@@ -1,6 +1,11 @@
 use ffi;
 
-use encoder::VpxEncoder;
+use encoder::controls::check_range;
+use encoder::header::FrameSizeParser;
+use encoder::{ContentType, Encoder, LayerId, ReferenceFlags, Vp9Level, VpxEncoder};
+use encoder::{SvcParameters, SvcRefFrameConfig};
+use error::{CodecError, Error, VPXResult as Result};
+use image::{ColorRange, ColorSpace};
 
 pub struct VP9;
 
@@ -10,25 +15,351 @@ impl VpxEncoder for VP9 {
     fn interface() -> *mut ffi::vpx_codec_iface_t {
         unsafe { &mut ffi::vpx_codec_vp9_cx_algo }
     }
+
+    fn cpu_used_range() -> (i32, i32) {
+        (-8, 8)
+    }
+
+    fn roi_block_size() -> u32 {
+        8
+    }
+
+    fn screen_share_cpu_used() -> i32 {
+        7
+    }
+
+    fn frame_size_parser() -> Option<FrameSizeParser> {
+        Some(FrameSizeParser::vp9())
+    }
+
+    fn set_content_type(encoder: &mut Encoder<Self>, content_type: ContentType) -> Result<()> {
+        let content = match content_type {
+            ContentType::Default => ffi::vp9e_tune_content::VP9E_CONTENT_DEFAULT,
+            ContentType::Screen => ffi::vp9e_tune_content::VP9E_CONTENT_SCREEN,
+            ContentType::Film => ffi::vp9e_tune_content::VP9E_CONTENT_FILM,
+        };
+        encoder.control(ffi::vp8e_enc_control_id::VP9E_SET_TUNE_CONTENT,
+                        content as i32,
+                        "VP9E_SET_TUNE_CONTENT")
+    }
+
+    fn set_target_level(encoder: &mut Encoder<Self>, level: Vp9Level) -> Result<()> {
+        encoder.set_target_level(level)
+    }
+
+    fn signal_color(encoder: &mut Encoder<Self>, space: ColorSpace, range: ColorRange)
+        -> Result<()>
+    {
+        let (explicit_space, explicit_range) = encoder.explicit_color;
+        if !explicit_space {
+            encoder.signal_color_space(space)?;
+        }
+        if !explicit_range {
+            encoder.signal_color_range(range)?;
+        }
+        Ok(())
+    }
+
+    fn enable_layers(encoder: &mut Encoder<Self>) -> Result<()> {
+        encoder.set_svc(true)
+    }
+
+    fn set_temporal_layer_id(encoder: &mut Encoder<Self>, layer_id: u32) -> Result<()> {
+        let spatial = encoder.layer_id.spatial;
+        encoder.set_svc_layer_id(LayerId { spatial: spatial, temporal: layer_id })
+    }
+}
+
+impl Encoder<VP9> {
+    /// Enables or disables the scalable (SVC) encoding. It is enabled automatically when the
+    /// encoder is configured with more than one spatial or temporal layer.
+    pub fn set_svc(&mut self, enabled: bool) -> Result<()> {
+        self.control(ffi::vp8e_enc_control_id::VP9E_SET_SVC, enabled as i32, "VP9E_SET_SVC")
+    }
+
+    /// Sets the scaling factors and quantizers of the spatial layers. The number of the layers
+    /// has to match the number of spatial layers in the configuration.
+    pub fn set_svc_parameters(&mut self, parameters: &SvcParameters) -> Result<()> {
+        if parameters.number_of_layers() != self.config.ss_number_layers {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "VP9E_SET_SVC_PARAMETERS",
+                format!("{} spatial layers are given, but {} are configured",
+                        parameters.number_of_layers(),
+                        self.config.ss_number_layers)));
+        }
+        let mut params = parameters.to_ffi(self.config.ts_number_layers)?;
+        self.control_ptr(ffi::vp8e_enc_control_id::VP9E_SET_SVC_PARAMETERS,
+                         &mut params,
+                         "VP9E_SET_SVC_PARAMETERS")?;
+        self.layer_scaling = parameters.scaling_factors();
+        Ok(())
+    }
+
+    /// Sets the reference frame buffers used by each spatial layer of the frame which is encoded
+    /// next. Usually called before every frame, since the prediction structure changes from frame
+    /// to frame.
+    pub fn set_svc_ref_frame_config(&mut self, config: &SvcRefFrameConfig) -> Result<()> {
+        let mut config = config.to_ffi()?;
+        self.control_ptr(ffi::vp8e_enc_control_id::VP9E_SET_SVC_REF_FRAME_CONFIG,
+                         &mut config,
+                         "VP9E_SET_SVC_REF_FRAME_CONFIG")
+    }
+
+    /// Enables or disables the lossless encoding (`VP9E_SET_LOSSLESS`). The decoded frames are
+    /// bit-exact copies of the input when used with `EncoderConfig::lossless()`.
+    pub fn set_lossless(&mut self, enabled: bool) -> Result<()> {
+        self.control(ffi::vp8e_enc_control_id::VP9E_SET_LOSSLESS,
+                     enabled as i32,
+                     "VP9E_SET_LOSSLESS")
+    }
+
+    /// Sets the adaptive quantization mode (`VP9E_SET_AQ_MODE`).
+    pub fn set_aq_mode(&mut self, mode: AqMode) -> Result<()> {
+        let mode: ffi::AQ_MODE = mode.into();
+        self.control(ffi::vp8e_enc_control_id::VP9E_SET_AQ_MODE, mode as i32, "VP9E_SET_AQ_MODE")
+    }
+
+    /// Enables or disables the special adaptive quantization for the alternate reference frames
+    /// (`VP9E_SET_ALT_REF_AQ`).
+    pub fn set_alt_ref_aq(&mut self, enabled: bool) -> Result<()> {
+        self.control(ffi::vp8e_enc_control_id::VP9E_SET_ALT_REF_AQ,
+                     enabled as i32,
+                     "VP9E_SET_ALT_REF_AQ")
+    }
+
+    /// Enables or disables the periodic quality boost of the frames
+    /// (`VP9E_SET_FRAME_PERIODIC_BOOST`), which improves the quality of static scenes.
+    pub fn set_frame_periodic_boost(&mut self, enabled: bool) -> Result<()> {
+        self.control(ffi::vp8e_enc_control_id::VP9E_SET_FRAME_PERIODIC_BOOST,
+                     enabled as i32,
+                     "VP9E_SET_FRAME_PERIODIC_BOOST")
+    }
+
+    /// Sets the number of tile columns as a base 2 logarithm (`VP9E_SET_TILE_COLUMNS`) in the
+    /// range 0..6. The value is clamped by libvpx to the range allowed by the frame width, each
+    /// tile column can be encoded and decoded in a separate thread.
+    pub fn set_tile_columns(&mut self, log2: u32) -> Result<()> {
+        check_range("VP9E_SET_TILE_COLUMNS", log2 as i32, 0, 6)?;
+        self.control(ffi::vp8e_enc_control_id::VP9E_SET_TILE_COLUMNS,
+                     log2 as i32,
+                     "VP9E_SET_TILE_COLUMNS")
+    }
+
+    /// Sets the number of tile columns which suits the configured frame width and number of
+    /// threads (see `auto_tile_columns()`).
+    pub fn set_auto_tile_columns(&mut self) -> Result<()> {
+        let log2 = auto_tile_columns(self.config.g_w, self.config.g_threads);
+        self.set_tile_columns(log2)
+    }
+
+    /// Sets the number of tile rows as a base 2 logarithm (`VP9E_SET_TILE_ROWS`) in the range
+    /// 0..2.
+    pub fn set_tile_rows(&mut self, log2: u32) -> Result<()> {
+        check_range("VP9E_SET_TILE_ROWS", log2 as i32, 0, 2)?;
+        self.control(ffi::vp8e_enc_control_id::VP9E_SET_TILE_ROWS,
+                     log2 as i32,
+                     "VP9E_SET_TILE_ROWS")
+    }
+
+    /// Enables or disables the row based multithreading (`VP9E_SET_ROW_MT`), which allows using
+    /// more threads than tile columns.
+    pub fn set_row_mt(&mut self, enabled: bool) -> Result<()> {
+        self.control(ffi::vp8e_enc_control_id::VP9E_SET_ROW_MT, enabled as i32, "VP9E_SET_ROW_MT")
+    }
+
+    /// Enables or disables the frame parallel decodability features
+    /// (`VP9E_SET_FRAME_PARALLEL_DECODING`), which let the decoder decode the frames in parallel
+    /// at the cost of a slightly worse compression.
+    pub fn set_frame_parallel_decoding(&mut self, enabled: bool) -> Result<()> {
+        self.control(ffi::vp8e_enc_control_id::VP9E_SET_FRAME_PARALLEL_DECODING,
+                     enabled as i32,
+                     "VP9E_SET_FRAME_PARALLEL_DECODING")
+    }
+
+    /// Sets the layer of the frames which are encoded next: the temporal layer and the lowest
+    /// spatial layer to encode. The frames returned by `frames_iter()` are tagged accordingly,
+    /// each spatial layer being a separate frame.
+    pub fn set_svc_layer_id(&mut self, layer_id: LayerId) -> Result<()> {
+        let mut id = ffi::vpx_svc_layer_id_t {
+            spatial_layer_id: layer_id.spatial as i32,
+            temporal_layer_id: layer_id.temporal as i32,
+        };
+        self.control_ptr(ffi::vp8e_enc_control_id::VP9E_SET_SVC_LAYER_ID,
+                         &mut id,
+                         "VP9E_SET_SVC_LAYER_ID")?;
+        self.layer_id = layer_id;
+        Ok(())
+    }
+
+    /// Sets the color space signaled in the stream (`VP9E_SET_COLOR_SPACE`). Otherwise it is set
+    /// automatically from the color space of the encoded images. sRGB needs a 4:4:4 profile, i.e.
+    /// the profile 1 or 3.
+    pub fn set_color_space(&mut self, space: ColorSpace) -> Result<()> {
+        self.signal_color_space(space)?;
+        self.explicit_color.0 = true;
+        Ok(())
+    }
+
+    /// Sets the color range signaled in the stream (`VP9E_SET_COLOR_RANGE`). Otherwise it is set
+    /// automatically from the color range of the encoded images.
+    pub fn set_color_range(&mut self, range: ColorRange) -> Result<()> {
+        self.signal_color_range(range)?;
+        self.explicit_color.1 = true;
+        Ok(())
+    }
+
+    fn signal_color_space(&mut self, space: ColorSpace) -> Result<()> {
+        if space == ColorSpace::SRGB && self.config.g_profile % 2 == 0 {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "VP9E_SET_COLOR_SPACE",
+                format!("sRGB cannot be used with the profile {}, only with the profiles 1 and 3",
+                        self.config.g_profile)));
+        }
+        let space: ffi::vpx_color_space_t = space.into();
+        self.control(ffi::vp8e_enc_control_id::VP9E_SET_COLOR_SPACE,
+                     space as i32,
+                     "VP9E_SET_COLOR_SPACE")
+    }
+
+    fn signal_color_range(&mut self, range: ColorRange) -> Result<()> {
+        let range: ffi::vpx_color_range_t = range.into();
+        self.control(ffi::vp8e_enc_control_id::VP9E_SET_COLOR_RANGE,
+                     range as i32,
+                     "VP9E_SET_COLOR_RANGE")
+    }
+
+    /// Sets the size the decoded frames should be rendered at (`VP9E_SET_RENDER_SIZE`), e.g. to
+    /// restore the original size of a frame which has been encoded at a lower resolution.
+    pub fn set_render_size(&mut self, width: u32, height: u32) -> Result<()> {
+        let mut size = [width as i32, height as i32];
+        self.control_ptr(ffi::vp8e_enc_control_id::VP9E_SET_RENDER_SIZE,
+                         &mut size,
+                         "VP9E_SET_RENDER_SIZE")
+    }
+}
+
+/// Minimum width of a tile column in pixels.
+const MIN_TILE_WIDTH: u32 = 256;
+/// Maximum width of a tile column in pixels.
+const MAX_TILE_WIDTH: u32 = 4096;
+/// Maximum number of tile columns as a base 2 logarithm.
+const MAX_TILE_COLUMNS_LOG2: u32 = 6;
+
+/// Picks the number of tile columns (as a base 2 logarithm) for the given frame width and number
+/// of threads: enough columns to keep all threads busy, but within the tile width limits of VP9.
+pub fn auto_tile_columns(width: u32, threads: u32) -> u32 {
+    let mut min_log2 = 0;
+    while (width >> min_log2) > MAX_TILE_WIDTH {
+        min_log2 += 1;
+    }
+    let mut max_log2 = 0;
+    while max_log2 < MAX_TILE_COLUMNS_LOG2 && (width >> (max_log2 + 1)) >= MIN_TILE_WIDTH {
+        max_log2 += 1;
+    }
+
+    let mut log2 = 0;
+    while log2 < MAX_TILE_COLUMNS_LOG2 && (1 << log2) < threads {
+        log2 += 1;
+    }
+    log2.max(min_log2).min(max_log2.max(min_log2))
+}
+
+/// Adaptive quantization mode, i.e. how the quantizer is varied across the blocks of a frame.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AqMode {
+    /// The same quantizer for the whole frame.
+    NoAq,
+    /// Based on the variance of the blocks, suits the VOD encoding.
+    Variance,
+    /// Based on the complexity of the blocks.
+    Complexity,
+    /// Refreshes a part of the frame with a better quality in every frame, suits the realtime
+    /// encoding.
+    CyclicRefresh,
+    /// For the 360 degree video in equirectangular projection.
+    Equator360,
+    /// Based on the temporal variance from the lookahead (only for the alternate reference
+    /// frames).
+    Lookahead,
+}
+
+impl Into<ffi::AQ_MODE> for AqMode {
+    fn into(self) -> ffi::AQ_MODE {
+        match self {
+            AqMode::NoAq => ffi::AQ_MODE::NO_AQ,
+            AqMode::Variance => ffi::AQ_MODE::VARIANCE_AQ,
+            AqMode::Complexity => ffi::AQ_MODE::COMPLEXITY_AQ,
+            AqMode::CyclicRefresh => ffi::AQ_MODE::CYCLIC_REFRESH_AQ,
+            AqMode::Equator360 => ffi::AQ_MODE::EQUATOR360_AQ,
+            AqMode::Lookahead => ffi::AQ_MODE::LOOKAHEAD_AQ,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub struct FrameFlags {
     force_kf: bool,
+    references: ReferenceFlags,
 }
 
 impl FrameFlags {
     pub fn keyframe(&mut self, keyframe: bool) {
         self.force_kf = keyframe;
     }
+
+    pub fn references(&mut self, references: ReferenceFlags) {
+        self.references = references;
+    }
+}
+
+impl From<ReferenceFlags> for FrameFlags {
+    fn from(references: ReferenceFlags) -> Self {
+        FrameFlags {
+            references: references,
+            ..Default::default()
+        }
+    }
 }
 
 impl Into<ffi::vpx_enc_frame_flags_t> for FrameFlags {
     fn into(self) -> ffi::vpx_enc_frame_flags_t {
-        let mut flags: ffi::vpx_enc_frame_flags_t = 0;
+        let mut flags: ffi::vpx_enc_frame_flags_t = self.references.into();
         if self.force_kf {
             flags |= ffi::VPX_EFLAG_FORCE_KF as i64;
         }
         flags
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::auto_tile_columns;
+
+    #[test]
+    fn tile_columns_follow_the_minimum_width() {
+        // Two columns need at least 2 * 256 pixels.
+        assert_eq!(auto_tile_columns(511, 8), 0);
+        assert_eq!(auto_tile_columns(512, 8), 1);
+        assert_eq!(auto_tile_columns(1023, 8), 1);
+        assert_eq!(auto_tile_columns(1024, 8), 2);
+    }
+
+    #[test]
+    fn tile_columns_follow_the_threads() {
+        assert_eq!(auto_tile_columns(1920, 1), 0);
+        assert_eq!(auto_tile_columns(1920, 2), 1);
+        assert_eq!(auto_tile_columns(1920, 3), 2);
+        // Clamped by the width and by the maximum of 64 columns.
+        assert_eq!(auto_tile_columns(1920, 100), 2);
+        assert_eq!(auto_tile_columns(1 << 20, 1000), 6);
+    }
+
+    #[test]
+    fn tile_columns_follow_the_maximum_width() {
+        // A tile column is at most 4096 pixels wide, regardless of the threads.
+        assert_eq!(auto_tile_columns(8192, 1), 1);
+        assert_eq!(auto_tile_columns(8193, 1), 2);
+    }
+}
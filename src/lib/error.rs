@@ -1,9 +1,12 @@
 //! General representation of the error in libvpx.
 
 use std;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
 use ffi;
 
-pub type VPXResult<T> = Result<T, CodecError>;
+pub type VPXResult<T> = Result<T, Error>;
 
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 /// Corresponds to the `vpx_codec_err_t` enum in C libvpx library.
@@ -59,8 +62,9 @@ impl std::fmt::Display for CodecError {
     }
 }
 
-impl std::error::Error for CodecError {
-    fn description(&self) -> &str {
+impl CodecError {
+    /// A human-readable description of the error.
+    pub fn message(&self) -> &'static str {
         match *self {
             CodecError::NoError => "No error occurred",
             CodecError::Unspecified => "Unspecified error",
@@ -76,9 +80,123 @@ impl std::error::Error for CodecError {
     }
 }
 
-pub fn check_err(value: ffi::vpx_codec_err_t) -> VPXResult<ffi::vpx_codec_err_t> {
+impl std::error::Error for CodecError {
+    fn description(&self) -> &str {
+        self.message()
+    }
+}
+
+/// An error which occurred during a particular libvpx operation. Besides the kind of the error,
+/// it carries the human-readable messages reported by the codec instance (if any), which usually
+/// explain what exactly went wrong (e.g. which configuration parameter is out of range).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Error {
+    kind: CodecError,
+    operation: &'static str,
+    message: Option<String>,
+    detail: Option<String>,
+}
+
+impl Error {
+    /// Creates an error of the given kind without any additional messages.
+    pub fn new(kind: CodecError, operation: &'static str) -> Self {
+        Error {
+            kind: kind,
+            operation: operation,
+            message: None,
+            detail: None,
+        }
+    }
+
+    /// Creates an error of the given kind with the detailed description of what went wrong. Used
+    /// for the errors which are detected by this crate before calling libvpx.
+    pub fn with_detail(kind: CodecError, operation: &'static str, detail: String) -> Self {
+        Error {
+            kind: kind,
+            operation: operation,
+            message: None,
+            detail: Some(detail),
+        }
+    }
+
+    /// Creates an error and fills its messages with `vpx_codec_error()` and
+    /// `vpx_codec_error_detail()` of the given codec context.
+    pub fn from_context(kind: CodecError,
+                        operation: &'static str,
+                        ctx: &mut ffi::vpx_codec_ctx_t) -> Self
+    {
+        let (message, detail) = unsafe {
+            (c_string(ffi::vpx_codec_error(ctx)), c_string(ffi::vpx_codec_error_detail(ctx)))
+        };
+        Error {
+            kind: kind,
+            operation: operation,
+            message: message,
+            detail: detail,
+        }
+    }
+
+    /// The kind of the error, suitable for matching.
+    pub fn kind(&self) -> CodecError {
+        self.kind
+    }
+
+    /// The name of the libvpx operation which failed.
+    pub fn operation(&self) -> &'static str {
+        self.operation
+    }
+
+    /// The error message returned by `vpx_codec_error()`.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_ref().map(|s| s.as_str())
+    }
+
+    /// The detailed error message returned by `vpx_codec_error_detail()`.
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_ref().map(|s| s.as_str())
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let message = self.message().unwrap_or_else(|| self.kind.message());
+        write!(fmt, "{} failed: {}", self.operation, message)?;
+        if let Some(detail) = self.detail() {
+            write!(fmt, ": {}", detail)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Copies a C string returned by libvpx, `None` for a null pointer.
+pub(crate) unsafe fn c_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+    }
+}
+
+pub fn check_err(value: ffi::vpx_codec_err_t,
+                 operation: &'static str) -> VPXResult<ffi::vpx_codec_err_t>
+{
+    if value != ffi::VPX_CODEC_OK {
+        Err(Error::new(CodecError::from(value), operation))
+    } else {
+        Ok(value)
+    }
+}
+
+/// Same as `check_err()`, but also collects the error messages from the codec context which has
+/// been used for the failed operation.
+pub fn check_ctx_err(ctx: &mut ffi::vpx_codec_ctx_t,
+                     value: ffi::vpx_codec_err_t,
+                     operation: &'static str) -> VPXResult<ffi::vpx_codec_err_t>
+{
     if value != ffi::VPX_CODEC_OK {
-        Err(CodecError::from(value))
+        Err(Error::from_context(CodecError::from(value), operation, ctx))
     } else {
         Ok(value)
     }
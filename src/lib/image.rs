@@ -1,14 +1,29 @@
 //! Wraps libvpx image representation which can be used to be passed in the video encoder.
 
 use std::borrow::Cow;
+use std::cmp;
+use std::convert::TryFrom;
+use std::mem;
 use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::slice;
 
 use ffi;
 
+use error::{CodecError, Error, VPXResult as Result};
+
 pub struct Image<'data> {
     inner: ffi::vpx_image_t,
     format: Format,
+    color_space: ColorSpace,
+    color_range: ColorRange,
+    bit_depth: u32,
     data: Cow<'data, [u8]>,
+    // The owned samples of a high bit-depth image, which are kept as `u16` to stay aligned.
+    samples: Vec<u16>,
+    // The memory each plane has been created with as (address, length), which is checked before
+    // reading the planes in Rust (see `scaled()`).
+    plane_spans: [(usize, usize); 4],
 }
 
 impl<'data> Image<'data> {
@@ -25,17 +40,253 @@ impl<'data> Image<'data> {
         unsafe { ffi::vpx_img_wrap(&mut img, fmt.into(), width, height,
                                    stride, data.as_ptr() as *mut _) };
         img.cs = color_space.into();
+        img.range = ColorRange::Studio.into();
+        let span = (data.as_ptr() as usize, data.len());
 
         Image {
             inner: img,
             format: fmt,
+            color_space: color_space,
+            color_range: ColorRange::Studio,
+            bit_depth: 8,
             data: data,
+            samples: Vec::new(),
+            plane_spans: [span; 4],
+        }
+    }
+
+    /// Creates a wrapper around a high bit-depth image data, each sample being stored in a `u16`.
+    /// The format has to be a high bit-depth one, and the samples have the given bit depth (up to
+    /// 16). **Does not check that the container is big enough.**
+    pub fn new_high_bit_depth(data: Cow<'data, [u16]>,
+                              fmt: Format,
+                              color_space: ColorSpace,
+                              bit_depth: u32,
+                              width: u32,
+                              height: u32,
+                              stride: u32) -> Result<Self>
+    {
+        if !fmt.is_high_bit_depth() {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "Image::new_high_bit_depth",
+                format!("{:?} is not a high bit-depth format", fmt)));
+        }
+        if bit_depth <= 8 || bit_depth > 16 {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "Image::new_high_bit_depth",
+                format!("invalid bit depth {}", bit_depth)));
+        }
+
+        // The samples are passed to libvpx in the native byte order.
+        let mut image = match data {
+            Cow::Borrowed(samples) => {
+                Image::new(Cow::Borrowed(sample_bytes(samples)),
+                           fmt, color_space, width, height, stride)
+            },
+            Cow::Owned(samples) => {
+                // The image owns the samples and never changes them, moving the vector into the
+                // image does not move its buffer, so the bytes stay valid as long as the image.
+                let bytes = unsafe {
+                    slice::from_raw_parts(samples.as_ptr() as *const u8, samples.len() * 2)
+                };
+                let mut image = Image::new(Cow::Borrowed(bytes),
+                                           fmt, color_space, width, height, stride);
+                image.samples = samples;
+                image
+            },
+        };
+        image.inner.bit_depth = bit_depth;
+        image.bit_depth = bit_depth;
+        Ok(image)
+    }
+
+    /// Creates an image from a Y plane and an interleaved UV plane with their own strides. If the
+    /// linked libvpx supports NV12 (the `nv12` feature), the planes are wrapped without copying,
+    /// otherwise they are copied into an I420 image.
+    pub fn from_nv12(y: &'data [u8],
+                     y_stride: u32,
+                     uv: &'data [u8],
+                     uv_stride: u32,
+                     color_space: ColorSpace,
+                     width: u32,
+                     height: u32) -> Result<Self>
+    {
+        let uv_width = (width as usize + 1) / 2 * 2;
+        let uv_height = (height as usize + 1) / 2;
+        let y_size = plane_size(y_stride as usize, height as usize, width as usize);
+        let uv_size = plane_size(uv_stride as usize, uv_height, uv_width);
+        if width == 0 || height == 0 || y_stride < width || (uv_stride as usize) < uv_width ||
+           y_size.map_or(true, |size| y.len() < size) ||
+           uv_size.map_or(true, |size| uv.len() < size) {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "Image::from_nv12",
+                format!("the planes are too small for {}x{}", width, height)));
+        }
+
+        nv12_image(y, y_stride, uv, uv_stride, color_space, width, height)
+    }
+
+    /// Creates an image from separate planes (in the Y, U, V and alpha order) with their own
+    /// strides in bytes, without copying the data. The number and the sizes of the planes are
+    /// validated against the format, which has to be an 8-bit planar one (`I420`, `YV12`, `I422`,
+    /// `I440`, `I444`, `I444A` or `NV12` with the `nv12` feature).
+    pub fn from_planes(planes: &[&'data [u8]],
+                       strides: &[u32],
+                       fmt: Format,
+                       color_space: ColorSpace,
+                       width: u32,
+                       height: u32) -> Result<Self>
+    {
+        let (count, x_shift, y_shift) = match fmt {
+            Format::I420 { hi_bit_depth: false } | Format::YV12 => (3, 1, 1),
+            Format::I422 { hi_bit_depth: false } => (3, 1, 0),
+            Format::I440 { hi_bit_depth: false } => (3, 0, 1),
+            Format::I444 { hi_bit_depth: false } => (3, 0, 0),
+            Format::I444A => (4, 0, 0),
+            #[cfg(feature = "nv12")]
+            Format::NV12 => (2, 1, 1),
+            _ => return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "Image::from_planes",
+                format!("{:?} is not a supported planar format", fmt))),
+        };
+        if planes.len() != count || strides.len() != count || width == 0 || height == 0 {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "Image::from_planes",
+                format!("{:?} needs {} planes of a non-empty image", fmt, count)));
+        }
+
+        for (index, (plane, &stride)) in planes.iter().zip(strides).enumerate() {
+            let chroma = index == 1 || index == 2;
+            let (x_shift, y_shift) = if chroma { (x_shift, y_shift) } else { (0, 0) };
+            let mut row_size = (width as usize + x_shift) >> x_shift;
+            if count == 2 && chroma {
+                // NV12, the U and V samples are interleaved.
+                row_size *= 2;
+            }
+            let rows = (height as usize + y_shift) >> y_shift;
+            let size = plane_size(stride as usize, rows, row_size);
+            if (stride as usize) < row_size || size.map_or(true, |size| plane.len() < size) {
+                return Err(Error::with_detail(
+                    CodecError::InvalidParam,
+                    "Image::from_planes",
+                    format!("the plane {} is too small for {}x{}", index, width, height)));
+            }
         }
+
+        let mut image = Image::new(Cow::Borrowed(planes[0]), fmt, color_space, width, height, 1);
+        for (index, (plane, &stride)) in planes.iter().zip(strides).enumerate() {
+            image.inner.planes[index] = plane.as_ptr() as *mut _;
+            image.inner.stride[index] = stride as i32;
+            image.plane_spans[index] = (plane.as_ptr() as usize, plane.len());
+        }
+        if count == 2 {
+            image.inner.planes[2] = planes[1][1..].as_ptr() as *mut _;
+            image.inner.stride[2] = strides[1] as i32;
+            image.plane_spans[2] = image.plane_spans[1];
+        }
+        Ok(image)
     }
 
     pub fn get_format(&self) -> &Format {
         &self.format
     }
+
+    /// The number of bits per sample, 8 unless the image has a high bit-depth format.
+    pub fn bit_depth(&self) -> u32 {
+        self.bit_depth
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Sets the range of the sample values, e.g. `ColorRange::Full` for the typical camera input.
+    /// The images are created with `ColorRange::Studio`.
+    pub fn set_color_range(&mut self, range: ColorRange) {
+        self.color_range = range;
+        self.inner.range = range.into();
+    }
+
+    pub fn color_range(&self) -> ColorRange {
+        self.color_range
+    }
+
+    /// Returns a copy of the image scaled to the given dimensions using a box filter. Only 8-bit
+    /// I420 images are supported. Fails if a plane does not fit into the data the image has been
+    /// created with.
+    pub fn scaled(&self, width: u32, height: u32) -> Result<Image<'static>> {
+        if self.format != (Format::I420 { hi_bit_depth: false }) {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "Image::scaled",
+                format!("scaling of {:?} images is not supported", self.format)));
+        }
+
+        if width == 0 || height == 0 {
+            return Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "Image::scaled",
+                format!("invalid dimensions {}x{}", width, height)));
+        }
+
+        // Same layout as `vpx_img_wrap()` produces with the stride alignment of 2.
+        let stride = (width as usize + 1) & !1;
+        let aligned_height = (height as usize + 1) & !1;
+        let luma_size = stride * aligned_height;
+        let chroma_size = (stride / 2) * (aligned_height / 2);
+        let mut data = vec![0u8; luma_size + 2 * chroma_size];
+        {
+            let (y, uv) = data.split_at_mut(luma_size);
+            let (u, v) = uv.split_at_mut(chroma_size);
+            for (plane, dst) in vec![y, u, v].into_iter().enumerate() {
+                let shift = if plane == 0 { 0 } else { 1 };
+                let src_width = (self.inner.d_w as usize + shift) >> shift;
+                let src_height = (self.inner.d_h as usize + shift) >> shift;
+                // A negative stride becomes too large to fit.
+                let src_stride = self.inner.stride[plane] as usize;
+                let src = Plane {
+                    data: self.plane_data(plane, plane_size(src_stride, src_height, src_width))?,
+                    stride: src_stride,
+                    width: src_width,
+                    height: src_height,
+                };
+                let dst = Plane {
+                    data: dst,
+                    stride: stride >> shift,
+                    width: (width as usize + shift) >> shift,
+                    height: (height as usize + shift) >> shift,
+                };
+                scale_plane(src, dst);
+            }
+        }
+
+        let mut image = Image::new(Cow::Owned(data), self.format, self.color_space,
+                                   width, height, 2);
+        image.set_color_range(self.color_range);
+        Ok(image)
+    }
+
+    /// Returns `size` bytes of the plane, failing unless they are within the memory the plane has
+    /// been created with.
+    fn plane_data(&self, plane: usize, size: Option<usize>) -> Result<&[u8]> {
+        let start = self.inner.planes[plane] as usize;
+        let (span_start, span_len) = self.plane_spans[plane];
+        let end = size.and_then(|size| start.checked_add(size));
+        match end {
+            Some(end) if start >= span_start && end <= span_start + span_len => {
+                Ok(unsafe { slice::from_raw_parts(self.inner.planes[plane], end - start) })
+            },
+            _ => Err(Error::with_detail(
+                CodecError::InvalidParam,
+                "Image::scaled",
+                format!("the plane {} does not fit into the image data", plane))),
+        }
+    }
 }
 
 impl<'data> Drop for Image<'data> {
@@ -58,6 +309,97 @@ impl<'data> DerefMut for Image<'data> {
     }
 }
 
+/// Wraps the validated NV12 planes without copying them.
+#[cfg(feature = "nv12")]
+fn nv12_image<'data>(y: &'data [u8],
+                     y_stride: u32,
+                     uv: &'data [u8],
+                     uv_stride: u32,
+                     color_space: ColorSpace,
+                     width: u32,
+                     height: u32) -> Result<Image<'data>>
+{
+    Image::from_planes(&[y, uv], &[y_stride, uv_stride], Format::NV12, color_space, width, height)
+}
+
+/// Copies the validated NV12 planes into an I420 image, for the libvpx versions without NV12.
+#[cfg(not(feature = "nv12"))]
+fn nv12_image<'data>(y: &'data [u8],
+                     y_stride: u32,
+                     uv: &'data [u8],
+                     uv_stride: u32,
+                     color_space: ColorSpace,
+                     width: u32,
+                     height: u32) -> Result<Image<'data>>
+{
+    let uv_width = (width as usize + 1) / 2 * 2;
+    let uv_height = (height as usize + 1) / 2;
+    // Same layout as `vpx_img_wrap()` produces with the stride alignment of 2.
+    let stride = (width as usize + 1) & !1;
+    let luma_size = stride * ((height as usize + 1) & !1);
+    let chroma_stride = stride / 2;
+    let chroma_size = luma_size / 4;
+    let mut data = vec![0u8; luma_size + 2 * chroma_size];
+    {
+        let (luma, chroma) = data.split_at_mut(luma_size);
+        let rows = luma.chunks_mut(stride).zip(y.chunks(y_stride as usize));
+        for (dst, src) in rows.take(height as usize) {
+            dst[..width as usize].copy_from_slice(&src[..width as usize]);
+        }
+        let (u, v) = chroma.split_at_mut(chroma_size);
+        let rows = u.chunks_mut(chroma_stride)
+                    .zip(v.chunks_mut(chroma_stride))
+                    .zip(uv.chunks(uv_stride as usize));
+        for ((u, v), src) in rows.take(uv_height) {
+            for (x, pair) in src[..uv_width].chunks(2).enumerate() {
+                u[x] = pair[0];
+                v[x] = pair[1];
+            }
+        }
+    }
+    Ok(Image::new(Cow::Owned(data), Format::I420 { hi_bit_depth: false }, color_space,
+                  width, height, 2))
+}
+
+/// Returns the number of bytes spanned by a plane of `rows` rows of `row_size` bytes with the
+/// given stride, `None` if it does not fit in `usize`.
+fn plane_size(stride: usize, rows: usize, row_size: usize) -> Option<usize> {
+    stride.checked_mul(rows.saturating_sub(1))?.checked_add(row_size)
+}
+
+/// Reinterprets the 16-bit samples as bytes.
+fn sample_bytes(samples: &[u16]) -> &[u8] {
+    unsafe { slice::from_raw_parts(samples.as_ptr() as *const u8, samples.len() * 2) }
+}
+
+/// A single plane of an image with its dimensions.
+struct Plane<Data> {
+    data: Data,
+    stride: usize,
+    width: usize,
+    height: usize,
+}
+
+/// Scales a single image plane using a box filter, i.e. each destination pixel is the average of
+/// the source pixels it covers.
+fn scale_plane(src: Plane<&[u8]>, dst: Plane<&mut [u8]>) {
+    for y in 0..dst.height {
+        let y0 = y * src.height / dst.height;
+        let y1 = cmp::max((y + 1) * src.height / dst.height, y0 + 1);
+        for x in 0..dst.width {
+            let x0 = x * src.width / dst.width;
+            let x1 = cmp::max((x + 1) * src.width / dst.width, x0 + 1);
+            let mut sum = 0;
+            for row in y0..y1 {
+                let row = &src.data[row * src.stride..];
+                sum += row[x0..x1].iter().map(|&p| p as usize).sum::<usize>();
+            }
+            let count = (y1 - y0) * (x1 - x0);
+            dst.data[y * dst.stride + x] = ((sum + count / 2) / count) as u8;
+        }
+    }
+}
+
 #[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 #[allow(non_camel_case_types)]
 pub enum Format {
@@ -78,6 +420,11 @@ pub enum Format {
 
     YV12,
 
+    /// A Y plane followed by an interleaved UV plane, both planes being subsampled as in I420.
+    /// Only available with the `nv12` feature, see `Image::from_nv12()`.
+    #[cfg(feature = "nv12")]
+    NV12,
+
     I420 { hi_bit_depth: bool },
     I422 { hi_bit_depth: bool },
     I440 { hi_bit_depth: bool },
@@ -87,6 +434,19 @@ pub enum Format {
     I444A,
 }
 
+impl Format {
+    /// Whether the samples are stored in 16 bits.
+    pub fn is_high_bit_depth(&self) -> bool {
+        match *self {
+            Format::I420 { hi_bit_depth } |
+            Format::I422 { hi_bit_depth } |
+            Format::I440 { hi_bit_depth } |
+            Format::I444 { hi_bit_depth } => hi_bit_depth,
+            _ => false,
+        }
+    }
+}
+
 impl Into<ffi::vpx_img_fmt_t> for Format {
     fn into(self) -> ffi::vpx_img_fmt_t {
         use self::Format::*;
@@ -113,15 +473,18 @@ impl Into<ffi::vpx_img_fmt_t> for Format {
 
             YV12 => VPX_IMG_FMT_YV12,
 
+            #[cfg(feature = "nv12")]
+            NV12 => VPX_IMG_FMT_NV12,
+
             I420 { hi_bit_depth: false } => VPX_IMG_FMT_I420,
             I422 { hi_bit_depth: false } => VPX_IMG_FMT_I422,
-            I440 { hi_bit_depth: false } => VPX_IMG_FMT_I444,
-            I444 { hi_bit_depth: false } => VPX_IMG_FMT_I440,
+            I440 { hi_bit_depth: false } => VPX_IMG_FMT_I440,
+            I444 { hi_bit_depth: false } => VPX_IMG_FMT_I444,
 
             I420 { hi_bit_depth: true } => VPX_IMG_FMT_I42016,
             I422 { hi_bit_depth: true } => VPX_IMG_FMT_I42216,
-            I440 { hi_bit_depth: true } => VPX_IMG_FMT_I44416,
-            I444 { hi_bit_depth: true } => VPX_IMG_FMT_I44016,
+            I440 { hi_bit_depth: true } => VPX_IMG_FMT_I44016,
+            I444 { hi_bit_depth: true } => VPX_IMG_FMT_I44416,
 
             /// Should be named `444A`.
             I444A => VPX_IMG_FMT_444A,
@@ -129,14 +492,87 @@ impl Into<ffi::vpx_img_fmt_t> for Format {
     }
 }
 
+/// All formats except `NV12`, which is only available with the `nv12` feature.
+const FORMATS: [Format; 25] = [
+    Format::RGB24,
+    Format::RGB32 { le: false, },
+    Format::RGB32 { le: true, },
+    Format::RGB565 { le: false, },
+    Format::RGB565 { le: true, },
+    Format::RGB555 { le: false, },
+    Format::RGB555 { le: true, },
+    Format::UYVY,
+    Format::YUY2,
+    Format::YVYU,
+    Format::BGR24,
+    Format::ARGB,
+    Format::BGRA,
+    Format::YV12_VPX,
+    Format::I420_VPX,
+    Format::YV12,
+    Format::I420 { hi_bit_depth: false },
+    Format::I422 { hi_bit_depth: false },
+    Format::I440 { hi_bit_depth: false },
+    Format::I444 { hi_bit_depth: false },
+    Format::I420 { hi_bit_depth: true },
+    Format::I422 { hi_bit_depth: true },
+    Format::I440 { hi_bit_depth: true },
+    Format::I444 { hi_bit_depth: true },
+    Format::I444A,
+];
+
+impl TryFrom<ffi::vpx_img_fmt_t> for Format {
+    type Error = Error;
+
+    fn try_from(fmt: ffi::vpx_img_fmt_t) -> Result<Self> {
+        Format::try_from(fmt as u32)
+    }
+}
+
+/// Converts the raw value of `vpx_img_fmt_t`, as stored in `vpx_image_t::fmt`. The value is taken
+/// as an integer, since an image coming from C may hold a value outside of the enum (see
+/// `enum_value()`).
+impl TryFrom<u32> for Format {
+    type Error = Error;
+
+    fn try_from(fmt: u32) -> Result<Self> {
+        #[cfg(feature = "nv12")]
+        let formats = FORMATS.iter().chain(&[Format::NV12]);
+        #[cfg(not(feature = "nv12"))]
+        let formats = FORMATS.iter();
+
+        formats.cloned()
+               .find(|&format| {
+                   let value: ffi::vpx_img_fmt_t = format.into();
+                   value as u32 == fmt
+               })
+               .ok_or_else(|| Error::with_detail(CodecError::InvalidParam,
+                                                 "Format::try_from",
+                                                 format!("unsupported image format {}", fmt)))
+    }
+}
+
+/// Reads a C enum field of a `vpx_image_t` as its integer value. Reading the field as the Rust
+/// enum is undefined behavior if C has stored a value which is not one of its variants.
+pub(crate) fn enum_value<T>(field: &T) -> u32 {
+    assert_eq!(mem::size_of::<T>(), mem::size_of::<u32>());
+    unsafe { ptr::read_unaligned(field as *const T as *const u32) }
+}
+
+/// The color space of an image, one variant per value of `vpx_color_space_t`.
+///
+/// Note: `Unknown` and `Reserved` were added so that every image coming from libvpx can be
+/// described, the exhaustive matches on this enum have to handle them.
 #[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 #[allow(non_camel_case_types)]
 pub enum ColorSpace {
+    Unknown,
     BT601,
     BT709,
     SMPTE170,
     SMPTE240,
     BT2020,
+    Reserved,
     SRGB,
 }
 
@@ -144,12 +580,76 @@ impl Into<ffi::vpx_color_space_t> for ColorSpace {
     fn into(self) -> ffi::vpx_color_space_t {
         use ffi::vpx_color_space::*;
         match self {
+            ColorSpace::Unknown => VPX_CS_UNKNOWN,
             ColorSpace::BT601 => VPX_CS_BT_601,
             ColorSpace::BT709 => VPX_CS_BT_709,
             ColorSpace::SMPTE170 => VPX_CS_SMPTE_170,
             ColorSpace::SMPTE240 => VPX_CS_SMPTE_240,
             ColorSpace::BT2020 => VPX_CS_BT_2020,
+            ColorSpace::Reserved => VPX_CS_RESERVED,
             ColorSpace::SRGB => VPX_CS_SRGB,
         }
     }
 }
+
+const COLOR_SPACES: [ColorSpace; 8] = [
+    ColorSpace::Unknown,
+    ColorSpace::BT601,
+    ColorSpace::BT709,
+    ColorSpace::SMPTE170,
+    ColorSpace::SMPTE240,
+    ColorSpace::BT2020,
+    ColorSpace::Reserved,
+    ColorSpace::SRGB,
+];
+
+impl TryFrom<ffi::vpx_color_space_t> for ColorSpace {
+    type Error = Error;
+
+    fn try_from(cs: ffi::vpx_color_space_t) -> Result<Self> {
+        ColorSpace::try_from(cs as u32)
+    }
+}
+
+/// Converts the raw value of `vpx_color_space_t`, as stored in `vpx_image_t::cs`. The value is
+/// taken as an integer, since an image coming from C may hold a value outside of the enum (see
+/// `enum_value()`).
+impl TryFrom<u32> for ColorSpace {
+    type Error = Error;
+
+    fn try_from(cs: u32) -> Result<Self> {
+        COLOR_SPACES.iter()
+                    .cloned()
+                    .find(|&color_space| {
+                        let value: ffi::vpx_color_space_t = color_space.into();
+                        value as u32 == cs
+                    })
+                    .ok_or_else(|| Error::with_detail(CodecError::InvalidParam,
+                                                      "ColorSpace::try_from",
+                                                      format!("unknown color space {}", cs)))
+    }
+}
+
+/// The range of the sample values.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum ColorRange {
+    /// The luma values are within 16..235 and the chroma values within 16..240 (for 8 bits).
+    Studio,
+    /// The values use the full range, i.e. 0..255 (for 8 bits).
+    Full,
+}
+
+impl Default for ColorRange {
+    fn default() -> Self {
+        ColorRange::Studio
+    }
+}
+
+impl Into<ffi::vpx_color_range_t> for ColorRange {
+    fn into(self) -> ffi::vpx_color_range_t {
+        match self {
+            ColorRange::Studio => ffi::vpx_color_range::VPX_CR_STUDIO_RANGE,
+            ColorRange::Full => ffi::vpx_color_range::VPX_CR_FULL_RANGE,
+        }
+    }
+}
@@ -6,7 +6,11 @@ extern crate libc;
 extern crate time;
 extern crate vpx_sys as ffi;
 
+pub mod capabilities;
 mod context;
+pub mod convert;
+pub mod decoder;
 pub mod encoder;
+pub mod error;
 pub mod image;
-mod error;
+pub mod version;
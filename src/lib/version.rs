@@ -0,0 +1,80 @@
+//! Information about the libvpx library which has been linked at runtime.
+
+use std;
+use std::ptr::null;
+
+use ffi;
+
+use context::CodecContext as Context;
+use encoder::EncoderConfig;
+use encoder::vp8::VP8;
+use error::{VPXResult as Result, c_string, check_ctx_err};
+
+/// The encoder ABI version this crate has been compiled against.
+pub const ENCODER_ABI_VERSION: i32 = ffi::VPX_ENCODER_ABI_VERSION as i32;
+
+/// The decoder ABI version this crate has been compiled against.
+pub const DECODER_ABI_VERSION: i32 = ffi::VPX_DECODER_ABI_VERSION as i32;
+
+/// Version of the libvpx library. Versions are ordered, so they can be compared in order to
+/// enable or disable features which depend on the particular library version.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Returns the version of the linked libvpx library (`vpx_codec_version()`).
+pub fn version() -> Version {
+    let version = unsafe { ffi::vpx_codec_version() } as u32;
+    Version {
+        major: (version >> 16) & 0xff,
+        minor: (version >> 8) & 0xff,
+        patch: version & 0xff,
+    }
+}
+
+/// Returns the version of the linked libvpx library as a printable string, e.g. `"v1.6.1"`.
+pub fn version_str() -> String {
+    unsafe { c_string(ffi::vpx_codec_version_str()) }.unwrap_or_default()
+}
+
+/// Returns the extra version information (e.g. git hash of the build) of the linked library.
+pub fn version_extra_str() -> String {
+    unsafe { c_string(ffi::vpx_codec_version_extra_str()) }.unwrap_or_default()
+}
+
+/// Returns the configuration options the linked library has been built with.
+pub fn build_config() -> String {
+    unsafe { c_string(ffi::vpx_codec_build_config()) }.unwrap_or_default()
+}
+
+/// Checks that the ABI of the linked library matches the ABI this crate has been compiled against.
+/// libvpx does not report its ABI version directly, so the check is done by initializing a VP8
+/// decoder and encoder, which fails with `CodecError::AbiMismatch` if the versions differ.
+pub fn check_abi() -> Result<()> {
+    let mut ctx = Context::new();
+    let res = unsafe { ffi::vpx_codec_dec_init_ver(&mut *ctx,
+                                                   &mut ffi::vpx_codec_vp8_dx_algo,
+                                                   null(),
+                                                   0,
+                                                   DECODER_ABI_VERSION) };
+    check_ctx_err(&mut ctx, res, "vpx_codec_dec_init")?;
+
+    let config: ffi::vpx_codec_enc_cfg_t = EncoderConfig::<VP8>::new()?.into();
+    let mut ctx = Context::new();
+    let res = unsafe { ffi::vpx_codec_enc_init_ver(&mut *ctx,
+                                                   &mut ffi::vpx_codec_vp8_cx_algo,
+                                                   &config,
+                                                   0,
+                                                   ENCODER_ABI_VERSION) };
+    check_ctx_err(&mut ctx, res, "vpx_codec_enc_init")?;
+    Ok(())
+}
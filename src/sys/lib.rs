@@ -34,5 +34,25 @@ impl Default for vpx_image_t {
         unsafe {::std::mem::zeroed()}
     }
 }
+impl Default for vpx_svc_extra_cfg_t {
+    fn default() -> Self {
+        unsafe {::std::mem::zeroed()}
+    }
+}
+impl Default for vpx_svc_ref_frame_config_t {
+    fn default() -> Self {
+        unsafe {::std::mem::zeroed()}
+    }
+}
+impl Default for vpx_roi_map_t {
+    fn default() -> Self {
+        unsafe {::std::mem::zeroed()}
+    }
+}
+impl Default for vpx_codec_stream_info_t {
+    fn default() -> Self {
+        unsafe {::std::mem::zeroed()}
+    }
+}
 
 include!(concat!(env!("OUT_DIR"), "/ffi.rs"));
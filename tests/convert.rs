@@ -0,0 +1,104 @@
+extern crate libvpx_rust as vpx;
+
+use vpx::convert::{self, PackedImage};
+use vpx::image::{ColorRange, ColorSpace, Format};
+
+const WIDTH: u32 = 6;
+const HEIGHT: u32 = 4;
+
+/// Generates a BGRA image with a gradient.
+fn generate_bgra() -> Vec<u8> {
+    let mut data = Vec::new();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            data.extend_from_slice(&[(x * 40) as u8, (y * 60) as u8, (x * y * 10) as u8, 0xff]);
+        }
+    }
+    data
+}
+
+#[test]
+fn bgra_round_trip() {
+    let data = generate_bgra();
+    let src = PackedImage::new(&data, Format::BGRA, WIDTH, HEIGHT, (WIDTH * 4) as usize).unwrap();
+    for &color_space in &[ColorSpace::BT601, ColorSpace::BT709] {
+        for &range in &[ColorRange::Studio, ColorRange::Full] {
+            let image = convert::to_i444(&src, color_space, range);
+            let result = unsafe { convert::to_packed(&image, Format::BGRA) }.unwrap();
+            assert_eq!(result.len(), data.len());
+            for (converted, original) in result.iter().zip(data.iter()) {
+                let diff = (*converted as i32 - *original as i32).abs();
+                assert!(diff <= 2, "{:?} {:?}: {} != {}", color_space, range, converted, original);
+            }
+        }
+    }
+}
+
+#[test]
+fn rgb24_primaries_to_i420() {
+    // Two columns of white, two of black and two of red.
+    let mut data = Vec::new();
+    for _ in 0..2 {
+        for pixel in &[[255, 255, 255], [255, 255, 255], [0, 0, 0], [0, 0, 0], [255, 0, 0],
+                       [255, 0, 0]] {
+            data.extend_from_slice(pixel);
+        }
+    }
+    let src = PackedImage::new(&data, Format::RGB24, 6, 2, 18).unwrap();
+
+    let image = convert::to_i420(&src, ColorSpace::BT601, ColorRange::Studio);
+    let rgb = unsafe { convert::to_packed(&image, Format::RGB24) }.unwrap();
+    assert_eq!(&rgb[..3], &[255, 255, 255]);
+    assert_eq!(&rgb[6..9], &[0, 0, 0]);
+    assert!(rgb[12] > 240 && rgb[13] < 15 && rgb[14] < 15, "{:?}", &rgb[12..15]);
+
+    let image = convert::to_i420(&src, ColorSpace::BT601, ColorRange::Full);
+    let luma = unsafe { std::slice::from_raw_parts(image.planes[0], 6) };
+    assert_eq!(luma, &[255, 255, 0, 0, 76, 76]);
+}
+
+#[test]
+fn yuy2_repacking() {
+    let data = [16, 100, 32, 200, 48, 110, 64, 210];
+    let src = PackedImage::new(&data, Format::YUY2, 4, 1, 8).unwrap();
+    let image = convert::to_i444(&src, ColorSpace::BT601, ColorRange::Studio);
+    assert_eq!(unsafe { convert::to_packed(&image, Format::YUY2) }.unwrap(), data.to_vec());
+    assert_eq!(unsafe { convert::to_packed(&image, Format::UYVY) }.unwrap(),
+               vec![100, 16, 200, 32, 110, 48, 210, 64]);
+}
+
+#[test]
+fn yuy2_chroma_is_averaged() {
+    // The two pixels of the pair have different chroma in I444.
+    let data = [16, 100, 32, 200];
+    let src = PackedImage::new(&data, Format::YUY2, 2, 1, 4).unwrap();
+    let image = convert::to_i444(&src, ColorSpace::BT601, ColorRange::Studio);
+    unsafe {
+        *image.planes[1].offset(1) = 110;
+        *image.planes[2].offset(1) = 211;
+    }
+    assert_eq!(unsafe { convert::to_packed(&image, Format::YUY2) }.unwrap(),
+               vec![16, 105, 32, 206]);
+}
+
+#[test]
+fn srgb_is_identity() {
+    let data = generate_bgra();
+    let src = PackedImage::new(&data, Format::BGRA, WIDTH, HEIGHT, (WIDTH * 4) as usize).unwrap();
+    let image = convert::to_i444(&src, ColorSpace::SRGB, ColorRange::Studio);
+    let size = (WIDTH * HEIGHT) as usize;
+    let plane = |index: usize| unsafe { std::slice::from_raw_parts(image.planes[index], size) };
+    for (i, pixel) in data.chunks(4).enumerate() {
+        // G, B and R.
+        assert_eq!((plane(0)[i], plane(1)[i], plane(2)[i]), (pixel[1], pixel[0], pixel[2]));
+    }
+    assert_eq!(unsafe { convert::to_packed(&image, Format::BGRA) }.unwrap(), data);
+}
+
+#[test]
+fn invalid_input() {
+    let data = [0u8; 16];
+    assert!(PackedImage::new(&data, Format::I420 { hi_bit_depth: false }, 2, 2, 2).is_err());
+    assert!(PackedImage::new(&data, Format::BGRA, 2, 3, 8).is_err());
+    assert!(PackedImage::new(&data, Format::BGRA, 2, 2, 4).is_err());
+}
@@ -0,0 +1,186 @@
+extern crate libvpx_rust as vpx;
+extern crate vpx_sys as ffi;
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+use ffi::vpx_color_space::*;
+use ffi::vpx_img_fmt::*;
+use vpx::image::{ColorSpace, Format, Image};
+
+/// Every `Format` with the libvpx format it corresponds to.
+fn formats() -> Vec<(Format, ffi::vpx_img_fmt_t)> {
+    vec![(Format::RGB24, VPX_IMG_FMT_RGB24),
+         (Format::RGB32 { le: false }, VPX_IMG_FMT_RGB32),
+         (Format::RGB32 { le: true }, VPX_IMG_FMT_RGB32_LE),
+         (Format::RGB565 { le: false }, VPX_IMG_FMT_RGB565),
+         (Format::RGB565 { le: true }, VPX_IMG_FMT_RGB565_LE),
+         (Format::RGB555 { le: false }, VPX_IMG_FMT_RGB555),
+         (Format::RGB555 { le: true }, VPX_IMG_FMT_RGB555_LE),
+         (Format::UYVY, VPX_IMG_FMT_UYVY),
+         (Format::YUY2, VPX_IMG_FMT_YUY2),
+         (Format::YVYU, VPX_IMG_FMT_YVYU),
+         (Format::BGR24, VPX_IMG_FMT_BGR24),
+         (Format::ARGB, VPX_IMG_FMT_ARGB),
+         (Format::BGRA, VPX_IMG_FMT_ARGB_LE),
+         (Format::YV12_VPX, VPX_IMG_FMT_VPXYV12),
+         (Format::I420_VPX, VPX_IMG_FMT_VPXI420),
+         (Format::YV12, VPX_IMG_FMT_YV12),
+         (Format::I420 { hi_bit_depth: false }, VPX_IMG_FMT_I420),
+         (Format::I422 { hi_bit_depth: false }, VPX_IMG_FMT_I422),
+         (Format::I440 { hi_bit_depth: false }, VPX_IMG_FMT_I440),
+         (Format::I444 { hi_bit_depth: false }, VPX_IMG_FMT_I444),
+         (Format::I420 { hi_bit_depth: true }, VPX_IMG_FMT_I42016),
+         (Format::I422 { hi_bit_depth: true }, VPX_IMG_FMT_I42216),
+         (Format::I440 { hi_bit_depth: true }, VPX_IMG_FMT_I44016),
+         (Format::I444 { hi_bit_depth: true }, VPX_IMG_FMT_I44416),
+         (Format::I444A, VPX_IMG_FMT_444A)]
+}
+
+/// Every `ColorSpace` with the libvpx color space it corresponds to.
+fn color_spaces() -> Vec<(ColorSpace, ffi::vpx_color_space_t)> {
+    vec![(ColorSpace::Unknown, VPX_CS_UNKNOWN),
+         (ColorSpace::BT601, VPX_CS_BT_601),
+         (ColorSpace::BT709, VPX_CS_BT_709),
+         (ColorSpace::SMPTE170, VPX_CS_SMPTE_170),
+         (ColorSpace::SMPTE240, VPX_CS_SMPTE_240),
+         (ColorSpace::BT2020, VPX_CS_BT_2020),
+         (ColorSpace::Reserved, VPX_CS_RESERVED),
+         (ColorSpace::SRGB, VPX_CS_SRGB)]
+}
+
+#[test]
+fn format_to_ffi() {
+    for (format, fmt) in formats() {
+        let converted: ffi::vpx_img_fmt_t = format.into();
+        assert_eq!(converted, fmt, "{:?}", format);
+    }
+}
+
+#[test]
+fn format_from_ffi() {
+    for (format, fmt) in formats() {
+        assert_eq!(Format::try_from(fmt).unwrap(), format, "{:?}", fmt);
+    }
+}
+
+#[test]
+fn format_round_trip() {
+    for (format, _) in formats() {
+        let fmt: ffi::vpx_img_fmt_t = format.into();
+        assert_eq!(Format::try_from(fmt).unwrap(), format);
+    }
+}
+
+#[test]
+fn format_from_raw_value() {
+    for (format, fmt) in formats() {
+        assert_eq!(Format::try_from(fmt as u32).unwrap(), format, "{:?}", fmt);
+    }
+    let unknown = formats().iter().map(|&(_, fmt)| fmt as u32).max().unwrap() + 1;
+    assert!(Format::try_from(unknown).is_err());
+}
+
+#[test]
+fn format_mapping_is_unique() {
+    let formats = formats();
+    for (i, &(_, fmt)) in formats.iter().enumerate() {
+        assert!(formats[i + 1..].iter().all(|&(_, other)| other != fmt), "{:?}", fmt);
+    }
+}
+
+#[test]
+fn format_none_is_rejected() {
+    assert!(Format::try_from(VPX_IMG_FMT_NONE).is_err());
+}
+
+#[test]
+fn color_space_round_trip() {
+    for (color_space, cs) in color_spaces() {
+        let converted: ffi::vpx_color_space_t = color_space.into();
+        assert_eq!(converted, cs, "{:?}", color_space);
+        assert_eq!(ColorSpace::try_from(cs).unwrap(), color_space, "{:?}", cs);
+        assert_eq!(ColorSpace::try_from(cs as u32).unwrap(), color_space, "{:?}", cs);
+    }
+}
+
+#[test]
+fn unknown_color_space_is_rejected() {
+    let last = color_spaces().iter().map(|&(_, cs)| cs as u32).max().unwrap();
+    assert!(ColorSpace::try_from(last + 1).is_err());
+}
+
+#[cfg(feature = "nv12")]
+#[test]
+fn nv12_round_trip() {
+    let converted: ffi::vpx_img_fmt_t = Format::NV12.into();
+    assert_eq!(converted, VPX_IMG_FMT_NV12);
+    assert_eq!(Format::try_from(VPX_IMG_FMT_NV12).unwrap(), Format::NV12);
+}
+
+#[test]
+fn nv12_planes() {
+    // A 4x2 image with padded rows.
+    let y = [1, 2, 3, 4, 0, 5, 6, 7, 8, 0];
+    let uv = [10, 20, 11, 21, 0, 0];
+    let image = Image::from_nv12(&y, 5, &uv, 6, ColorSpace::BT601, 4, 2).unwrap();
+    let plane = |plane: usize, len: usize| unsafe {
+        std::slice::from_raw_parts(image.planes[plane], len)
+    };
+    #[cfg(feature = "nv12")]
+    {
+        assert_eq!(*image.get_format(), Format::NV12);
+        assert_eq!((plane(1, 4), plane(2, 3)), (&uv[..4], &uv[1..4]));
+    }
+    #[cfg(not(feature = "nv12"))]
+    {
+        assert_eq!(*image.get_format(), Format::I420 { hi_bit_depth: false });
+        assert_eq!(image.stride[0], 4);
+        assert_eq!(plane(0, 8), &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!((plane(1, 2), plane(2, 2)), (&[10, 11][..], &[20, 21][..]));
+    }
+    assert!(Image::from_nv12(&y, 5, &uv[..3], 6, ColorSpace::BT601, 4, 2).is_err());
+}
+
+#[test]
+fn separate_planes() {
+    // A 3x3 I420 image with padded rows, the chroma planes being 2x2.
+    let y = &[0u8; 3 * 8][..];
+    let u = &[0u8; 2 * 4][..];
+    let v = &[0u8; 4 + 2][..];
+    let i420 = Format::I420 { hi_bit_depth: false };
+    let image = Image::from_planes(&[y, u, v], &[8, 4, 4], i420, ColorSpace::BT709, 3, 3)
+        .unwrap();
+    assert_eq!((image.d_w, image.d_h), (3, 3));
+    assert_eq!(&image.stride[..3], &[8, 4, 4]);
+    assert_eq!(image.planes[0] as *const u8, y.as_ptr());
+    assert_eq!(image.planes[1] as *const u8, u.as_ptr());
+    assert_eq!(image.planes[2] as *const u8, v.as_ptr());
+
+    // The V plane is one sample short.
+    assert!(Image::from_planes(&[y, u, &v[..5]], &[8, 4, 4], i420, ColorSpace::BT709, 3, 3)
+        .is_err());
+    // The I444 chroma planes are not subsampled.
+    let i444 = Format::I444 { hi_bit_depth: false };
+    assert!(Image::from_planes(&[y, u, v], &[8, 4, 4], i444, ColorSpace::BT709, 3, 3)
+        .is_err());
+    // Missing plane and stride smaller than the width.
+    assert!(Image::from_planes(&[y, u], &[8, 4], i420, ColorSpace::BT709, 3, 3).is_err());
+    assert!(Image::from_planes(&[y, u, v], &[2, 4, 4], i420, ColorSpace::BT709, 3, 3)
+        .is_err());
+}
+
+#[test]
+fn scaled_checks_the_planes() {
+    // A 4x4 I420 image takes 16 + 2 * 4 bytes.
+    let data = [100u8; 24];
+    let i420 = Format::I420 { hi_bit_depth: false };
+    let image = Image::new(Cow::Borrowed(&data[..]), i420, ColorSpace::BT601, 4, 4, 1);
+    let scaled = image.scaled(2, 2).unwrap();
+    assert_eq!((scaled.d_w, scaled.d_h), (2, 2));
+    assert_eq!(unsafe { *scaled.planes[0] }, 100);
+
+    // The V plane does not fit into the data.
+    let image = Image::new(Cow::Borrowed(&data[..20]), i420, ColorSpace::BT601, 4, 4, 1);
+    assert!(image.scaled(2, 2).is_err());
+}
@@ -0,0 +1,94 @@
+extern crate libvpx_rust as vpx;
+
+use std::borrow::Cow;
+use std::mem;
+
+use vpx::encoder::{BitDepth, CodecFlags, Deadline, Encoder, EncoderConfig};
+use vpx::encoder::vp9::{FrameFlags, VP9};
+use vpx::error::CodecError;
+use vpx::image::{ColorSpace, Format, Image};
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+
+fn highbitdepth_flags() -> CodecFlags {
+    let mut flags = CodecFlags::default();
+    flags.highbitdepth(true);
+    flags
+}
+
+fn high_bit_depth_image(fmt: Format, bit_depth: u32) -> Image<'static> {
+    let samples = vec![1u16 << (bit_depth - 1); (WIDTH * HEIGHT * 3) as usize];
+    Image::new_high_bit_depth(Cow::Owned(samples),
+                              fmt,
+                              ColorSpace::BT709,
+                              bit_depth,
+                              WIDTH,
+                              HEIGHT,
+                              1).unwrap()
+}
+
+fn assert_rejected(encoder: &mut Encoder<VP9>, image: &Image) {
+    let error = encoder.encode(image, 0, 1, FrameFlags::default(), Deadline::Realtime)
+                       .unwrap_err();
+    assert_eq!((error.kind(), error.operation()), (CodecError::InvalidParam, "vpx_codec_encode"));
+}
+
+#[test]
+fn owned_samples_stay_aligned() {
+    let image = high_bit_depth_image(Format::I420 { hi_bit_depth: true }, 10);
+    for plane in 0..3 {
+        assert_eq!(image.planes[plane] as usize % mem::align_of::<u16>(), 0);
+    }
+}
+
+#[test]
+fn bit_depth_must_match_the_profile() {
+    let config = EncoderConfig::<VP9>::profile_2(BitDepth::Ten).unwrap();
+    assert!(config.validate_bit_depth(&highbitdepth_flags()).is_ok());
+    let error = config.validate_bit_depth(&CodecFlags::default()).unwrap_err();
+    assert_eq!(error.kind(), CodecError::InvalidParam);
+
+    let mut config = EncoderConfig::<VP9>::new().unwrap();
+    assert!(config.validate_bit_depth(&CodecFlags::default()).is_ok());
+    config.set_profile(2);
+    assert!(config.validate_bit_depth(&highbitdepth_flags()).is_err());
+    config.set_profile(4);
+    assert!(config.validate_bit_depth(&CodecFlags::default()).is_err());
+
+    let mut config = EncoderConfig::<VP9>::profile_3(BitDepth::Ten).unwrap();
+    config.set_input_bit_depth(12);
+    assert!(config.validate_bit_depth(&highbitdepth_flags()).is_err());
+}
+
+#[test]
+fn images_must_match_the_encoder() {
+    let mut config = EncoderConfig::<VP9>::new().unwrap();
+    config.set_frame_dimensions(WIDTH, HEIGHT);
+    config.set_timebase(1, 30);
+    let mut encoder = Encoder::<VP9>::new(Some(config), None).unwrap();
+    assert_rejected(&mut encoder, &high_bit_depth_image(Format::I420 { hi_bit_depth: true }, 10));
+
+    if !vpx::encoder::capabilities::<VP9>().highbitdepth() {
+        return;
+    }
+
+    let mut config = EncoderConfig::<VP9>::profile_2(BitDepth::Ten).unwrap();
+    config.set_frame_dimensions(WIDTH, HEIGHT);
+    config.set_timebase(1, 30);
+    let mut encoder = Encoder::<VP9>::new(Some(config), Some(highbitdepth_flags())).unwrap();
+
+    let data = vec![128u8; (WIDTH * HEIGHT * 3 / 2) as usize];
+    let image = Image::new(Cow::Borrowed(&data),
+                           Format::I420 { hi_bit_depth: false },
+                           ColorSpace::BT709,
+                           WIDTH,
+                           HEIGHT,
+                           1);
+    assert_rejected(&mut encoder, &image);
+    assert_rejected(&mut encoder, &high_bit_depth_image(Format::I444 { hi_bit_depth: true }, 10));
+    assert_rejected(&mut encoder, &high_bit_depth_image(Format::I420 { hi_bit_depth: true }, 12));
+
+    let image = high_bit_depth_image(Format::I420 { hi_bit_depth: true }, 10);
+    encoder.encode(&image, 0, 1, FrameFlags::default(), Deadline::Realtime).unwrap();
+}
@@ -0,0 +1,80 @@
+extern crate libvpx_rust as vpx;
+
+use std::borrow::Cow;
+
+use vpx::decoder::Decoder;
+use vpx::encoder::{Deadline, Encoder, EncoderConfig, PacketKind};
+use vpx::encoder::vp9::{FrameFlags, VP9};
+use vpx::image::{ColorSpace, Format, Image};
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+const FRAMES: usize = 5;
+
+/// Generates an I420 frame with some texture which changes from frame to frame.
+fn generate_frame(index: usize) -> Vec<u8> {
+    let chroma_size = (WIDTH / 2 * HEIGHT / 2) as usize;
+    let mut data = Vec::with_capacity((WIDTH * HEIGHT) as usize + 2 * chroma_size);
+    for y in 0..HEIGHT as usize {
+        for x in 0..WIDTH as usize {
+            data.push(((x * 7 + y * 13 + index * 5) ^ (x * y)) as u8);
+        }
+    }
+    for i in 0..chroma_size {
+        data.push((i * 3 + index) as u8);
+    }
+    for i in 0..chroma_size {
+        data.push(!((i * 5 + index) as u8));
+    }
+    data
+}
+
+#[test]
+fn vp9_lossless_round_trip() {
+    let mut config = EncoderConfig::<VP9>::lossless().unwrap();
+    config.set_frame_dimensions(WIDTH, HEIGHT);
+    config.set_timebase(1, 30);
+    config.set_lag_in_frames(0);
+
+    let frames: Vec<Vec<u8>> = (0..FRAMES).map(generate_frame).collect();
+    let mut encoded = Vec::new();
+    {
+        let mut encoder = Encoder::<VP9>::new(Some(config), None).unwrap();
+        encoder.set_lossless(true).unwrap();
+        for (pts, frame) in frames.iter().enumerate() {
+            let image = Image::new(Cow::Borrowed(frame),
+                                   Format::I420 { hi_bit_depth: false },
+                                   ColorSpace::BT601,
+                                   WIDTH,
+                                   HEIGHT,
+                                   1);
+            encoder.encode(&image, pts as i64, 1, FrameFlags::default(), Deadline::GoodQuality)
+                   .unwrap();
+            for packet in encoder.frames_iter() {
+                if let PacketKind::EncodedFrame(frame) = packet {
+                    encoded.push(frame.data().to_vec());
+                }
+            }
+        }
+    }
+    assert_eq!(encoded.len(), FRAMES);
+
+    let mut decoder = Decoder::<VP9>::new().unwrap();
+    for (original, data) in frames.iter().zip(encoded.iter()) {
+        decoder.decode(data).unwrap();
+        let image = decoder.frames_iter().next().expect("No decoded image");
+        assert_eq!((image.width(), image.height()), (WIDTH, HEIGHT));
+
+        let mut offset = 0;
+        for plane in 0..3 {
+            let width = image.plane_width(plane);
+            for row in 0..image.plane_height(plane) {
+                let start = row * image.stride(plane);
+                assert_eq!(&image.plane(plane)[start..start + width],
+                           &original[offset..offset + width],
+                           "Plane {} row {} differs", plane, row);
+                offset += width;
+            }
+        }
+    }
+}